@@ -0,0 +1,137 @@
+//! Account rules: what a valid username and password look like, how many failed
+//! logins lock an account and for how long, and the typed errors the account
+//! functions in `db` return.
+
+use std::fmt;
+
+/// Username and password policy plus login rate limiting. `CsApp` holds one and
+/// passes it to the `db` account functions.
+#[derive(Debug, Clone)]
+pub struct AccountRules {
+    pub min_username_len: usize,
+    pub max_username_len: usize,
+    /// Characters allowed in usernames besides ASCII letters and digits.
+    pub username_symbols: &'static str,
+    pub min_password_len: usize,
+    pub password_needs_letter: bool,
+    pub password_needs_digit: bool,
+    /// Failed logins within `failure_window_secs` that lock the username.
+    pub max_failed_logins: u32,
+    pub failure_window_secs: i64,
+    pub lockout_secs: i64,
+}
+
+impl Default for AccountRules {
+    fn default() -> Self {
+        AccountRules {
+            min_username_len: 3,
+            max_username_len: 20,
+            username_symbols: "_-.",
+            min_password_len: 8,
+            password_needs_letter: true,
+            password_needs_digit: true,
+            max_failed_logins: 5,
+            failure_window_secs: 15 * 60,
+            lockout_secs: 5 * 60,
+        }
+    }
+}
+
+impl AccountRules {
+    /// Check `username` against the length and charset rules. Uniqueness is
+    /// checked by the database.
+    pub fn check_username(&self, username: &str) -> Result<(), AuthError> {
+        let len = username.chars().count();
+        if len < self.min_username_len || len > self.max_username_len {
+            return Err(AuthError::UsernameLength { min: self.min_username_len, max: self.max_username_len });
+        }
+        if !username.chars().all(|c| c.is_ascii_alphanumeric() || self.username_symbols.contains(c)) {
+            return Err(AuthError::UsernameChars { symbols: self.username_symbols });
+        }
+        Ok(())
+    }
+
+    pub fn check_password(&self, password: &str) -> Result<(), AuthError> {
+        if password.chars().count() < self.min_password_len {
+            return Err(AuthError::PasswordTooShort { min: self.min_password_len });
+        }
+        if (self.password_needs_letter && !password.chars().any(|c| c.is_alphabetic()))
+            || (self.password_needs_digit && !password.chars().any(|c| c.is_ascii_digit()))
+        {
+            return Err(AuthError::PasswordTooWeak);
+        }
+        Ok(())
+    }
+}
+
+/// Why registering, logging in or changing account details failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    UsernameLength { min: usize, max: usize },
+    /// Only ASCII letters, digits and `symbols` are allowed.
+    UsernameChars { symbols: &'static str },
+    /// Another account has this name, ignoring case.
+    UsernameTaken,
+    PasswordTooShort { min: usize },
+    /// The password lacks a letter or digit required by the rules.
+    PasswordTooWeak,
+    /// Unknown username or wrong password on login.
+    InvalidCredentials,
+    /// Wrong current password when changing or deleting an account.
+    IncorrectPassword,
+    /// Too many failed logins; retry after this many seconds.
+    LockedOut { retry_after_secs: i64 },
+    UserNotFound,
+    Storage(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::UsernameLength { min, max } => write!(f, "username must be {}-{} characters", min, max),
+            AuthError::UsernameChars { .. } => write!(f, "username contains invalid characters"),
+            AuthError::UsernameTaken => write!(f, "username taken"),
+            AuthError::PasswordTooShort { min } => write!(f, "password shorter than {} characters", min),
+            AuthError::PasswordTooWeak => write!(f, "password misses a required letter or digit"),
+            AuthError::InvalidCredentials => write!(f, "invalid username or password"),
+            AuthError::IncorrectPassword => write!(f, "incorrect password"),
+            AuthError::LockedOut { retry_after_secs } => write!(f, "locked for {} more seconds", retry_after_secs),
+            AuthError::UserNotFound => write!(f, "user not found"),
+            AuthError::Storage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<String> for AuthError {
+    fn from(e: String) -> Self {
+        AuthError::Storage(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn username_rules() {
+        let rules = AccountRules::default();
+        assert_eq!(rules.check_username("ab"), Err(AuthError::UsernameLength { min: 3, max: 20 }));
+        assert_eq!(rules.check_username(""), Err(AuthError::UsernameLength { min: 3, max: 20 }));
+        assert_eq!(rules.check_username("a".repeat(21).as_str()), Err(AuthError::UsernameLength { min: 3, max: 20 }));
+        assert_eq!(rules.check_username("bad name"), Err(AuthError::UsernameChars { symbols: "_-." }));
+        assert_eq!(rules.check_username("bäd"), Err(AuthError::UsernameChars { symbols: "_-." }));
+        assert!(rules.check_username("Good_name-1.x").is_ok());
+    }
+
+    #[test]
+    fn password_policy() {
+        let rules = AccountRules::default();
+        assert_eq!(rules.check_password("abc1"), Err(AuthError::PasswordTooShort { min: 8 }));
+        assert_eq!(rules.check_password("abcdefgh"), Err(AuthError::PasswordTooWeak));
+        assert_eq!(rules.check_password("12345678"), Err(AuthError::PasswordTooWeak));
+        assert!(rules.check_password("abcdefg1").is_ok());
+
+        let lenient = AccountRules { min_password_len: 1, password_needs_letter: false, password_needs_digit: false, ..rules };
+        assert!(lenient.check_password("x").is_ok());
+    }
+}
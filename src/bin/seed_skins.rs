@@ -1,6 +1,11 @@
 use std::fs;
 use serde::Deserialize;
 
+// Share the domain types (rarity validation, money) with the app.
+#[allow(dead_code)]
+#[path = "../models.rs"]
+mod models;
+
 #[derive(Deserialize)]
 struct SeedSkin {
     name: String,
@@ -9,6 +14,9 @@ struct SeedSkin {
     collection: Option<String>,
     weapon_type: Option<String>,
     image_base64: Option<String>,
+    image_path: Option<String>,
+    min_float: Option<f64>,
+    max_float: Option<f64>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,9 +26,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let content = fs::read_to_string(json_path)?;
     let list: Vec<SeedSkin> = serde_json::from_str(&content)?;
 
-    for s in list {
+    // Reject the whole file if any rarity is unknown, before touching the DB
+    let mut rarities = Vec::with_capacity(list.len());
+    for s in &list {
+        let rarity = match s.rarity.as_deref() {
+            None => None,
+            Some(r) => Some(models::Rarity::parse(r).ok_or_else(|| {
+                let known: Vec<&str> = models::Rarity::ALL.iter().map(|r| r.as_str()).collect();
+                format!("unknown rarity '{}' for '{}' (expected one of: {})", r, s.name, known.join(", "))
+            })?),
+        };
+        rarities.push(rarity);
+    }
+
+    for (s, rarity) in list.iter().zip(rarities) {
         // call into crate::db by constructing a small one-off connection using rusqlite
-        let _ = CsTradeUp_seed::seed_one(db_path, &s);
+        let _ = cs_trade_up_seed::seed_one(db_path, s, rarity);
     }
 
     println!("Seeding finished (best-effort).");
@@ -28,32 +49,71 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 // Local small helper module to avoid importing the whole crate as a binary
-mod CsTradeUp_seed {
+mod cs_trade_up_seed {
     use rusqlite::params;
+    use base64::Engine as _;
     use super::SeedSkin;
+    use super::models::{Money, Rarity};
 
-    pub fn seed_one(db_path: &str, s: &SeedSkin) -> Result<(), String> {
+    pub fn seed_one(db_path: &str, s: &SeedSkin, rarity: Option<Rarity>) -> Result<(), String> {
         let conn = rusqlite::Connection::open(db_path).map_err(|e| e.to_string())?;
-        conn.execute(
+        conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS skins (
                 id INTEGER PRIMARY KEY,
                 name TEXT NOT NULL UNIQUE,
                 rarity TEXT,
-                price REAL DEFAULT 0.0,
+                price INTEGER NOT NULL DEFAULT 0,
                 collection TEXT,
                 weapon_type TEXT,
-                image_base64 TEXT
-            )",
-            [],
+                min_float REAL DEFAULT 0.0,
+                max_float REAL DEFAULT 1.0
+            );
+            CREATE TABLE IF NOT EXISTS skin_images (
+                skin_id INTEGER PRIMARY KEY,
+                image BLOB NOT NULL,
+                FOREIGN KEY(skin_id) REFERENCES skins(id) ON DELETE CASCADE
+            );",
         )
         .map_err(|e| e.to_string())?;
 
         conn.execute(
-            "INSERT OR IGNORE INTO skins (name, rarity, price, collection, weapon_type, image_base64) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![s.name, s.rarity, s.price, s.collection, s.weapon_type, s.image_base64],
+            "INSERT OR IGNORE INTO skins (name, rarity, price, base_price, collection, weapon_type, min_float, max_float) VALUES (?1, ?2, ?3, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                s.name,
+                rarity,
+                Money::from_dollars(s.price.unwrap_or(0.0)),
+                s.collection,
+                s.weapon_type,
+                s.min_float.unwrap_or(0.0),
+                s.max_float.unwrap_or(1.0)
+            ],
         )
         .map_err(|e| e.to_string())?;
 
+        if let Some(image) = load_image(s)? {
+            conn.execute(
+                "INSERT OR IGNORE INTO skin_images (skin_id, image) SELECT id, ?2 FROM skins WHERE name = ?1",
+                params![s.name, image],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
         Ok(())
     }
+
+    /// Image bytes from `image_path` (relative to `data/`, or a `file://` URL) or else
+    /// `image_base64` (raw or a `data:` URI).
+    fn load_image(s: &SeedSkin) -> Result<Option<Vec<u8>>, String> {
+        if let Some(path) = &s.image_path {
+            let path = path.strip_prefix("file://").unwrap_or(path);
+            let bytes = std::fs::read(std::path::Path::new("data").join(path)).map_err(|e| format!("{}: {}", path, e))?;
+            return Ok(Some(bytes));
+        }
+        let Some(text) = &s.image_base64 else { return Ok(None) };
+        let data = match text.strip_prefix("data:") {
+            Some(uri) => uri.split_once(',').map(|(_, d)| d).unwrap_or(""),
+            None => text,
+        };
+        base64::engine::general_purpose::STANDARD.decode(data.trim()).map(Some).map_err(|e| e.to_string())
+    }
 }
@@ -1,89 +1,127 @@
-use rusqlite::{params, Connection, Result};
-use crate::models::User;
+use base64::Engine as _;
+use rusqlite::{params, Connection, OptionalExtension, Result, Transaction};
+use crate::accounts::{AccountRules, AuthError};
+use crate::models::{InventoryValue, LedgerEntry, LedgerKind, Money, PricePoint, Rarity, SkinFilter, SkinSort, User, ValueGroup, Variant, Wear};
+
+mod migrations;
 
 type DbResult<T> = std::result::Result<T, String>;
-pub fn init_db(path: &str) -> Result<()> {
-    let conn = Connection::open(path)?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY,
-            username TEXT NOT NULL UNIQUE,
-            password TEXT NOT NULL,
-            balance REAL DEFAULT 100.0
-        )",
-        [],
-    )?;
-
-    // Migration: ensure older DBs get the `balance` column so SELECTs that include it don't fail.
-    // If the column is missing, add it and initialize existing rows to the default starting balance.
-    {
-        let mut has_balance = false;
-        let mut info_stmt = conn.prepare("PRAGMA table_info(users)")?;
-        let mut rows = info_stmt.query([])?;
-        while let Some(row) = rows.next()? {
-            let name: String = row.get(1)?;
-            if name == "balance" {
-                has_balance = true;
-                break;
+
+/// Balance every new account starts with.
+const STARTING_BALANCE: Money = Money::from_cents(10000);
+
+/// Handle on the app's SQLite database. Owns a single connection for the life of
+/// the app, with WAL journaling and foreign keys enabled; statements are prepared
+/// once and cached on the connection.
+pub struct Database {
+    conn: Connection,
+    path: String,
+}
+
+impl Database {
+    /// Open (or create) the database at `path`, bring its schema up to date and seed
+    /// the catalog from `data/`.
+    pub fn open(path: &str) -> Result<Database> {
+        Self::init(Connection::open(path)?, path)
+    }
+
+    /// A throwaway database, used when the database file cannot be opened.
+    pub fn open_in_memory() -> Result<Database> {
+        Self::init(Connection::open_in_memory()?, ":memory:")
+    }
+
+    fn init(mut conn: Connection, path: &str) -> Result<Database> {
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.set_prepared_statement_cache_capacity(64);
+        migrations::migrate(&mut conn)?;
+
+        let db = Database { conn, path: path.to_string() };
+        db.seed();
+        Ok(db)
+    }
+
+    /// File the database lives in.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Start a transaction. Every method called on `self` while it is open runs
+    /// inside it; dropping it without `commit` rolls back.
+    pub fn transaction(&self) -> DbResult<Transaction<'_>> {
+        self.conn.unchecked_transaction().map_err(|e| e.to_string())
+    }
+
+    /// Seed the catalog from the developer-provided `data/skins.json` and
+    /// `data/cases.json`, if present. Best-effort: invalid entries are skipped.
+    fn seed(&self) {
+        // If a developer-provided data/skins.json exists, seed those skins into the catalog.
+        let seed_path = std::path::Path::new("data/skins.json");
+        let seed_dir = seed_path.parent().unwrap_or(std::path::Path::new("."));
+        if seed_path.exists()
+            && let Ok(json) = std::fs::read_to_string(seed_path)
+            && let Ok(list) = serde_json::from_str::<Vec<SeedSkin>>(&json)
+        {
+            for s in list {
+                let rarity = match s.rarity.as_deref().map(|r| parse_seed_rarity(&s.name, r)).transpose() {
+                    Ok(r) => r,
+                    Err(e) => {
+                        eprintln!("Skipping seed skin: {}", e);
+                        continue;
+                    }
+                };
+                let skin = self.add_skin(
+                    &s.name,
+                    rarity,
+                    Money::from_dollars(s.price.unwrap_or(0.0)),
+                    s.collection.as_deref(),
+                    s.weapon_type.as_deref(),
+                    None,
+                    (s.min_float.unwrap_or(0.0), s.max_float.unwrap_or(1.0)),
+                );
+                // Only read and decode the image the first time the skin is seeded
+                if let Ok(skin) = skin
+                    && !skin.has_image
+                    && let Some(image) = s.load_image(seed_dir)
+                {
+                    let _ = self.set_skin_image(skin.id, &image);
+                }
             }
         }
 
-        if !has_balance {
-            conn.execute("ALTER TABLE users ADD COLUMN balance REAL DEFAULT 100.0", [])?;
-            conn.execute("UPDATE users SET balance = 100.0 WHERE balance IS NULL", [])?;
-        }
-    }
-
-    // Inventory table: keeps track of skins owned by users. Each row represents
-    // a single copy owned by a user. Duplicates are allowed by inserting
-    // multiple rows for the same skin_id/user_id.
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS inventory (
-            id INTEGER PRIMARY KEY,
-            user_id INTEGER NOT NULL,
-            skin_id INTEGER NOT NULL,
-            FOREIGN KEY(user_id) REFERENCES users(id),
-            FOREIGN KEY(skin_id) REFERENCES skins(id)
-        )",
-        [],
-    )?;
-
-    // Catalog of available skins
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS skins (
-            id INTEGER PRIMARY KEY,
-            name TEXT NOT NULL UNIQUE,
-            rarity TEXT,
-            price REAL DEFAULT 0.0,
-            collection TEXT,
-            weapon_type TEXT,
-            image_base64 TEXT
-        )",
-        [],
-    )?;
-
-    // If a developer-provided data/skins.json exists, seed those skins into the catalog.
-    let seed_path = std::path::Path::new("data/skins.json");
-    if seed_path.exists() {
-        if let Ok(json) = std::fs::read_to_string(seed_path) {
-            if let Ok(list) = serde_json::from_str::<Vec<SeedSkin>>(&json) {
-                for s in list {
-                    let _ = add_skin(
-                        path,
-                        &s.name,
-                        s.rarity.as_deref(),
-                        s.price.unwrap_or(0.0),
-                        s.collection.as_deref(),
-                        s.weapon_type.as_deref(),
-                        s.image_base64.as_deref(),
-                    );
-                }
+        // Case definitions live in data/cases.json next to the skins seed.
+        let cases_path = std::path::Path::new("data/cases.json");
+        if cases_path.exists()
+            && let Ok(json) = std::fs::read_to_string(cases_path)
+            && let Ok(list) = serde_json::from_str::<Vec<SeedCase>>(&json)
+        {
+            for c in list {
+                let odds: DbResult<Vec<(Rarity, f64)>> = c
+                    .odds
+                    .into_iter()
+                    .map(|(r, o)| Ok((parse_seed_rarity(&c.name, &r)?, o)))
+                    .collect();
+                let odds = match odds {
+                    Ok(o) => o,
+                    Err(e) => {
+                        eprintln!("Skipping seed case: {}", e);
+                        continue;
+                    }
+                };
+                let _ = self.add_case(
+                    &c.name,
+                    Money::from_dollars(c.price),
+                    Money::from_dollars(c.key_price),
+                    c.image_base64.as_deref(),
+                    &c.collections,
+                    &c.rare_special_collections,
+                    &odds,
+                );
             }
         }
-    }
 
-    Ok(())
+    }
 }
 
 // Helper struct for seeding from JSON (local to db.rs)
@@ -95,281 +133,1332 @@ struct SeedSkin {
     collection: Option<String>,
     weapon_type: Option<String>,
     image_base64: Option<String>,
+    /// Image file, relative to the seed file's directory or as a `file://` URL.
+    /// Takes precedence over `image_base64`.
+    image_path: Option<String>,
+    min_float: Option<f64>,
+    max_float: Option<f64>,
 }
 
-/// Create a user. Returns Ok(()) on success, or Err(String) with a friendly message on failure. Fx if username is taken then we return a friendly error.
-pub fn create_user(path: &str, username: &str, password: &str) -> DbResult<User> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+impl SeedSkin {
+    /// The entry's image bytes, from `image_path` or else `image_base64`.
+    fn load_image(&self, seed_dir: &std::path::Path) -> Option<Vec<u8>> {
+        if let Some(path) = &self.image_path {
+            let path = path.strip_prefix("file://").unwrap_or(path);
+            match std::fs::read(seed_dir.join(path)) {
+                Ok(bytes) => return Some(bytes),
+                Err(e) => eprintln!("Skipping image for '{}': {}: {}", self.name, path, e),
+            }
+        }
+        self.image_base64.as_deref().and_then(decode_image_base64)
+    }
+}
 
-    // Check if username already exists
-    let existing: i64 = conn
-        .query_row(
-            "SELECT COUNT(1) FROM users WHERE username = ?1",
-            params![username],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+/// Decode an image stored as base64 text, either raw or as a `data:` URI.
+pub(crate) fn decode_image_base64(text: &str) -> Option<Vec<u8>> {
+    let data = match text.strip_prefix("data:") {
+        Some(uri) => &uri[uri.find(',')? + 1..],
+        None => text,
+    };
+    base64::engine::general_purpose::STANDARD.decode(data.trim()).ok()
+}
 
-    if existing > 0 {
-        return Err("username taken".into());
-    }
+/// Validate a seed entry's rarity against `Rarity`, rejecting unknown values with
+/// an error naming the entry.
+fn parse_seed_rarity(entry: &str, rarity: &str) -> DbResult<Rarity> {
+    Rarity::parse(rarity).ok_or_else(|| {
+        let known: Vec<&str> = Rarity::ALL.iter().map(|r| r.as_str()).collect();
+        format!("unknown rarity '{}' for '{}' (expected one of: {})", rarity, entry, known.join(", "))
+    })
+}
 
-    conn.execute(
-        "INSERT INTO users (username, password, balance) VALUES (?1, ?2, 100.0)",
-        params![username, password],
+// Helper struct for seeding cases from JSON
+#[derive(serde::Deserialize)]
+struct SeedCase {
+    name: String,
+    price: f64,
+    key_price: f64,
+    image_base64: Option<String>,
+    collections: Vec<String>,
+    #[serde(default)]
+    rare_special_collections: Vec<String>,
+    /// Optional drop odds (percent) keyed by rarity; omitted means default CS odds.
+    #[serde(default)]
+    odds: std::collections::BTreeMap<String, f64>,
+}
+
+/// Column list matching `skin_from_row`.
+/// Images live in `skin_images`; only whether one exists is selected here.
+const SKIN_COLUMNS: &str = "id, name, rarity, price, collection, weapon_type,
+                    EXISTS(SELECT 1 FROM skin_images im WHERE im.skin_id = skins.id), min_float, max_float, base_price";
+
+/// Build a `Skin` from a row whose skin columns start at index `base`, in `SKIN_COLUMNS` order.
+fn skin_from_row(row: &rusqlite::Row, base: usize) -> Result<crate::models::Skin> {
+    let price: Money = row.get(base + 3)?;
+    Ok(crate::models::Skin {
+        id: row.get(base)?,
+        name: row.get(base + 1)?,
+        rarity: row.get(base + 2)?,
+        price,
+        base_price: row.get::<_, Option<Money>>(base + 9)?.unwrap_or(price),
+        collection: row.get(base + 4)?,
+        weapon_type: row.get(base + 5)?,
+        has_image: row.get(base + 6)?,
+        min_float: row.get::<_, Option<f64>>(base + 7)?.unwrap_or(0.0),
+        max_float: row.get::<_, Option<f64>>(base + 8)?.unwrap_or(1.0),
+    })
+}
+
+/// Seconds since the Unix epoch.
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Inventory columns joined with the skin columns, matching `owned_from_row`.
+const OWNED_COLUMNS: &str = "i.id, i.user_id, i.skin_id, i.float_value, i.wear, i.variant,
+                    s.id, s.name, s.rarity, s.price, s.collection, s.weapon_type,
+                    EXISTS(SELECT 1 FROM skin_images im WHERE im.skin_id = s.id), s.min_float, s.max_float, s.base_price";
+
+/// Market value in cents of an `inventory i LEFT JOIN skins s` row: the live price
+/// scaled by the wear and variant multipliers and rounded, exactly as
+/// `scripts::sell::item_value` computes it.
+fn owned_value_sql() -> String {
+    let wear: String = Wear::ALL
+        .iter()
+        .map(|w| format!(" WHEN '{}' THEN {:?}", w.as_str(), w.price_multiplier()))
+        .collect();
+    let variant: String = Variant::ALL
+        .iter()
+        .map(|v| format!(" WHEN '{}' THEN {:?}", v.as_str(), v.price_multiplier()))
+        .collect();
+    format!(
+        "CAST(ROUND(COALESCE(s.price, 0) * (CASE i.wear{} ELSE 1.0 END * CASE i.variant{} ELSE 1.0 END)) AS INTEGER)",
+        wear, variant
     )
-    .map_err(|e| e.to_string())?;
+}
+
+/// Build an `OwnedSkin` from an `inventory i LEFT JOIN skins s` row selecting `OWNED_COLUMNS`.
+fn owned_from_row(row: &rusqlite::Row) -> Result<crate::models::OwnedSkin> {
+    let float_value: f64 = row.get(3)?;
+    let inv = crate::models::InventoryItem {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        skin_id: row.get(2)?,
+        float_value,
+        wear: row.get::<_, Option<Wear>>(4)?.unwrap_or_else(|| Wear::from_float(float_value)),
+        variant: row.get(5)?,
+    };
 
-    let id = conn.last_insert_rowid();
+    // skin fields may be NULL (if skin_id is null or missing)
+    let skin_id: Option<i64> = row.get(6)?;
+    let skin = if skin_id.is_some() {
+        Some(skin_from_row(row, 6)?)
+    } else {
+        None
+    };
+
+    Ok(crate::models::OwnedSkin { inventory: inv, skin })
+}
 
-    Ok(User {
-        id,
-        username: username.to_string(),
-        password: password.to_string(),
-        balance: 100.0,
+fn case_from_row(row: &rusqlite::Row) -> Result<crate::models::Case> {
+    Ok(crate::models::Case {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        price: row.get(2)?,
+        key_price: row.get(3)?,
+        image_base64: row.get(4)?,
+        collections: Vec::new(),
+        rare_special_collections: Vec::new(),
+        odds: Vec::new(),
     })
 }
 
-pub fn authenticate(path: &str, username: &str, password: &str) -> DbResult<Option<User>> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+impl Database {
+    /// Create a user after checking `rules`. Fails with a typed error if the username or
+    /// password breaks a rule or the name is taken (ignoring case).
+    pub fn create_user(&self, rules: &AccountRules, username: &str, password: &str) -> Result<User, AuthError> {
+        rules.check_username(username)?;
+        rules.check_password(password)?;
 
-    let mut stmt = conn
-        .prepare("SELECT id, username, password, balance FROM users WHERE username = ?1")
+        // Hash before opening the transaction: Argon2 is deliberately slow
+        let password_hash = crate::password::hash_password(password)?;
+
+        let tx = self.transaction()?;
+
+        // Check if username already exists
+        if self.username_taken(username, None)? {
+            return Err(AuthError::UsernameTaken);
+        }
+
+        tx.execute(
+            "INSERT INTO users (username, password_hash, balance) VALUES (?1, ?2, ?3)",
+            params![username, password_hash, STARTING_BALANCE],
+        )
         .map_err(|e| e.to_string())?;
 
-    let mut rows = stmt
-        .query_map(params![username], |row| {
-            Ok(User {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password: row.get(2)?,
-                balance: row.get(3)?,
-            })
+        let id = tx.last_insert_rowid();
+        self.record_ledger(id, STARTING_BALANCE, LedgerKind::Deposit, None, None)?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        Ok(User {
+            id,
+            username: username.to_string(),
+            balance: STARTING_BALANCE,
         })
+    }
+
+    /// Check a username / password pair and return the user. The username is matched
+    /// ignoring case. Wrong passwords are counted per username, and after
+    /// `rules.max_failed_logins` failures within the window the name is locked for
+    /// `rules.lockout_secs`. A legacy plaintext password that matches is replaced by
+    /// its Argon2id hash.
+    pub fn authenticate(&self, rules: &AccountRules, username: &str, password: &str) -> Result<User, AuthError> {
+        self.authenticate_at(rules, username, password, unix_now())
+    }
+
+    fn authenticate_at(&self, rules: &AccountRules, username: &str, password: &str, now: i64) -> Result<User, AuthError> {
+
+        let locked_until: Option<i64> = self.conn
+            .query_row(
+                "SELECT locked_until FROM login_failures WHERE username = ?1 COLLATE NOCASE",
+                params![username],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .flatten();
+        if let Some(until) = locked_until
+            && until > now
+        {
+            return Err(AuthError::LockedOut { retry_after_secs: until - now });
+        }
+
+        let found = self.conn.query_row(
+            "SELECT id, username, balance, password_hash FROM users WHERE username = ?1 COLLATE NOCASE",
+            params![username],
+            |row| {
+                let user = User { id: row.get(0)?, username: row.get(1)?, balance: row.get(2)? };
+                Ok((user, row.get::<_, String>(3)?))
+            },
+        )
+        .optional()
         .map_err(|e| e.to_string())?;
 
-    if let Some(res) = rows.next() {
-        let user = res.map_err(|e| e.to_string())?;
-        if user.password == password {
-            Ok(Some(user))
+        // An unknown name still pays for one Argon2 verification, so the response
+        // time doesn't reveal which usernames exist
+        let verified = match &found {
+            Some((_, stored)) => crate::password::verify_password(password, stored),
+            None => {
+                crate::password::verify_dummy(password);
+                false
+            }
+        };
+        let Some((user, stored)) = found.filter(|_| verified) else {
+            self.record_login_failure(rules, username, now)?;
+            return Err(AuthError::InvalidCredentials);
+        };
+
+        self.conn.execute("DELETE FROM login_failures WHERE username = ?1 COLLATE NOCASE", params![username])
+            .map_err(|e| e.to_string())?;
+
+        if !crate::password::is_hashed(&stored) {
+            let hash = crate::password::hash_password(password)?;
+            self.conn.execute("UPDATE users SET password_hash = ?1 WHERE id = ?2", params![hash, user.id])
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(user)
+    }
+
+    /// Count a failed login for `username`. A failure outside the current window
+    /// starts a new one; reaching the limit locks the name and resets the count.
+    fn record_login_failure(&self, rules: &AccountRules, username: &str, now: i64) -> DbResult<()> {
+        let window_start = now - rules.failure_window_secs;
+        self.conn.execute(
+            "INSERT INTO login_failures (username, failures, window_start) VALUES (?1, 1, ?2)
+             ON CONFLICT(username) DO UPDATE SET
+                failures = CASE WHEN window_start < ?3 THEN 1 ELSE failures + 1 END,
+                window_start = CASE WHEN window_start < ?3 THEN ?2 ELSE window_start END",
+            params![username, now, window_start],
+        )
+        .map_err(|e| e.to_string())?;
+        self.conn.execute(
+            "UPDATE login_failures SET locked_until = ?2, failures = 0, window_start = ?3
+             WHERE username = ?1 COLLATE NOCASE AND failures >= ?4",
+            params![username, now + rules.lockout_secs, now, rules.max_failed_logins],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Whether `username` (ignoring case) belongs to an account other than `except_user`.
+    fn username_taken(&self, username: &str, except_user: Option<i64>) -> DbResult<bool> {
+        let existing: i64 = self.conn
+            .query_row(
+                "SELECT COUNT(1) FROM users WHERE username = ?1 COLLATE NOCASE AND id IS NOT ?2",
+                params![username, except_user],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(existing > 0)
+    }
+
+    /// Fail unless `password` is `user_id`'s current password.
+    fn check_password(&self, user_id: i64, password: &str) -> Result<(), AuthError> {
+        let stored: String = self.conn
+            .query_row("SELECT password_hash FROM users WHERE id = ?1", params![user_id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or(AuthError::UserNotFound)?;
+
+        if crate::password::verify_password(password, &stored) {
+            Ok(())
         } else {
-            Ok(None)
+            Err(AuthError::IncorrectPassword)
         }
-    } else {
-        Ok(None)
     }
-}
 
-/// Add an inventory item for a user. Returns the inserted InventoryItem on success.
-/// Insert an inventory item referring to an existing catalog skin id.
-pub fn add_inventory_item(
-    path: &str,
-    user_id: i64,
-    skin_id: i64,
-) -> DbResult<crate::models::InventoryItem> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "INSERT INTO inventory (user_id, skin_id) VALUES (?1, ?2)",
-        params![user_id, skin_id],
-    )
-    .map_err(|e| e.to_string())?;
+    /// Replace `user_id`'s password. `old_password` must match the current one and
+    /// the new one must satisfy `rules`.
+    pub fn change_password(
+        &self,
+        rules: &AccountRules,
+        user_id: i64,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<(), AuthError> {
+        rules.check_password(new_password)?;
 
-    let id = conn.last_insert_rowid();
+        self.check_password(user_id, old_password)?;
 
-    Ok(crate::models::InventoryItem { id, user_id, skin_id })
-}
+        let hash = crate::password::hash_password(new_password)?;
+        self.conn.execute("UPDATE users SET password_hash = ?1 WHERE id = ?2", params![hash, user_id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
 
-/// Get a user by id
-pub fn get_user_by_id(path: &str, user_id: i64) -> DbResult<Option<crate::models::User>> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare("SELECT id, username, password, balance FROM users WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+    /// Change `user_id`'s username, applying the same rules and uniqueness check as
+    /// `create_user`.
+    pub fn rename_user(&self, rules: &AccountRules, user_id: i64, new_username: &str) -> Result<User, AuthError> {
+        rules.check_username(new_username)?;
 
-    match stmt.query_row(params![user_id], |row| {
-        Ok(crate::models::User {
-            id: row.get(0)?,
-            username: row.get(1)?,
-            password: row.get(2)?,
-            balance: row.get(3)?,
-        })
-    }) {
-        Ok(u) => Ok(Some(u)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
+        let tx = self.transaction()?;
+
+        if self.username_taken(new_username, Some(user_id))? {
+            return Err(AuthError::UsernameTaken);
+        }
+
+        let changed = tx
+            .execute("UPDATE users SET username = ?1 WHERE id = ?2", params![new_username, user_id])
+            .map_err(|e| e.to_string())?;
+        if changed == 0 {
+            return Err(AuthError::UserNotFound);
+        }
+
+        let balance = self.user_balance(user_id)?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        Ok(User { id: user_id, username: new_username.to_string(), balance })
     }
-}
 
-/// Change (add or subtract) a user's balance by `delta` and return the new balance.
-pub fn change_user_balance(path: &str, user_id: i64, delta: f64) -> DbResult<f64> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-    conn.execute(
-        "UPDATE users SET balance = balance + ?1 WHERE id = ?2",
-        params![delta, user_id],
-    )
-    .map_err(|e| e.to_string())?;
+    /// Delete `user_id` after checking `password`, together with everything that
+    /// belongs to the account (sessions, inventory and ledger rows), in one transaction.
+    pub fn delete_user(&self, user_id: i64, password: &str) -> Result<(), AuthError> {
+        let tx = self.transaction()?;
+
+        self.check_password(user_id, password)?;
+
+        for table in ["sessions", "ledger", "inventory"] {
+            tx.execute(&format!("DELETE FROM {} WHERE user_id = ?1", table), params![user_id])
+                .map_err(|e| e.to_string())?;
+        }
+        tx.execute("DELETE FROM users WHERE id = ?1", params![user_id])
+            .map_err(|e| e.to_string())?;
 
-    // Return updated balance
-    let new_balance: f64 = conn
-        .query_row("SELECT balance FROM users WHERE id = ?1", params![user_id], |r| r.get(0))
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Start a session for `user_id` that expires after `ttl_secs`. Returns its token.
+    pub fn create_session(&self, user_id: i64, ttl_secs: i64) -> DbResult<String> {
+        let token = crate::session::new_token();
+        let now = unix_now();
+        self.conn.execute(
+            "INSERT INTO sessions (token, user_id, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+            params![token, user_id, now, now + ttl_secs],
+        )
         .map_err(|e| e.to_string())?;
+        Ok(token)
+    }
 
-    Ok(new_balance)
-}
+    /// The user a session token belongs to, or `None` if the token is unknown,
+    /// revoked or expired. Expired sessions are deleted on the way.
+    pub fn session_user(&self, token: &str) -> DbResult<Option<User>> {
+        self.conn.execute("DELETE FROM sessions WHERE expires_at <= ?1", params![unix_now()])
+            .map_err(|e| e.to_string())?;
 
-/// Insert an inventory item referring to an existing catalog skin id.
-// (removed add_inventory_item_with_skin_id - inventory now references skins by id only)
+        self.conn.query_row(
+            "SELECT u.id, u.username, u.balance FROM sessions s JOIN users u ON u.id = s.user_id WHERE s.token = ?1",
+            params![token],
+            |row| Ok(User { id: row.get(0)?, username: row.get(1)?, balance: row.get(2)? }),
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
 
-/// Add a skin to the canonical catalog. Returns the inserted Skin.
-pub fn add_skin(
-    path: &str,
-    name: &str,
-    rarity: Option<&str>,
-    price: f64,
-    collection: Option<&str>,
-    weapon_type: Option<&str>,
-    image_base64: Option<&str>,
-) -> DbResult<crate::models::Skin> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "INSERT OR IGNORE INTO skins (name, rarity, price, collection, weapon_type, image_base64) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![name, rarity, price, collection, weapon_type, image_base64],
-    )
-    .map_err(|e| e.to_string())?;
+    /// End a session. Revoking an unknown token is not an error.
+    pub fn revoke_session(&self, token: &str) -> DbResult<()> {
+        self.conn.execute("DELETE FROM sessions WHERE token = ?1", params![token])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
 
-    // Return the skin (fetch id)
-    let mut stmt = conn
-    .prepare("SELECT id, name, rarity, price, collection, weapon_type, image_base64 FROM skins WHERE name = ?1")
+    /// Add a copy of catalog skin `skin_id` to `user_id`'s inventory and return it.
+    /// The wear tier is derived from `float_value`. Joins an open transaction.
+    pub fn add_inventory_item(
+        &self,
+        user_id: i64,
+        skin_id: i64,
+        float_value: f64,
+        variant: Variant,
+    ) -> DbResult<crate::models::InventoryItem> {
+        let wear = Wear::from_float(float_value);
+
+        self.conn.execute(
+            "INSERT INTO inventory (user_id, skin_id, float_value, wear, variant) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![user_id, skin_id, float_value, wear, variant],
+        )
         .map_err(|e| e.to_string())?;
 
-    let skin = stmt
-        .query_row(params![name], |row| {
-            Ok(crate::models::Skin {
+        let id = self.conn.last_insert_rowid();
+
+        Ok(crate::models::InventoryItem { id, user_id, skin_id, float_value, wear, variant })
+    }
+
+    /// Get a user by id
+    pub fn get_user_by_id(&self, user_id: i64) -> DbResult<Option<crate::models::User>> {
+        let mut stmt = self.conn
+            .prepare_cached("SELECT id, username, balance FROM users WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+
+        match stmt.query_row(params![user_id], |row| {
+            Ok(crate::models::User {
                 id: row.get(0)?,
-                name: row.get(1)?,
-                rarity: row.get(2)?,
-                price: row.get(3)?,
-                collection: row.get(4)?,
-                weapon_type: row.get(5)?,
-        image_base64: row.get(6)?,
+                username: row.get(1)?,
+                balance: row.get(2)?,
             })
-        })
-        .map_err(|e| e.to_string())?;
+        }) {
+            Ok(u) => Ok(Some(u)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
 
-    Ok(skin)
-}
+    /// Current balance of `user_id`, read on an open connection or transaction.
+    /// Fails with "User not found" for unknown users.
+    pub fn user_balance(&self, user_id: i64) -> DbResult<Money> {
+        let mut stmt = self.conn
+            .prepare_cached("SELECT balance FROM users WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+        match stmt.query_row(params![user_id], |r| r.get(0)) {
+            Ok(b) => Ok(b),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err("User not found".into()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
 
-pub fn get_skin_by_name(path: &str, name: &str) -> DbResult<Option<crate::models::Skin>> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare("SELECT id, name, rarity, price, collection, weapon_type, image_base64 FROM skins WHERE name = ?1")
+    /// Take `amount` from a user's balance, record it in the ledger as `kind` with the
+    /// related skin / inventory ids, and return the new balance. Fails without changing
+    /// anything if the user does not exist or cannot afford it. Meant to run inside the
+    /// caller's transaction.
+    pub fn debit_balance(
+        &self,
+        user_id: i64,
+        amount: Money,
+        kind: LedgerKind,
+        skin_id: Option<i64>,
+        inventory_id: Option<i64>,
+    ) -> DbResult<Money> {
+        let changed = self.conn
+            .execute(
+                "UPDATE users SET balance = balance - ?1 WHERE id = ?2 AND balance >= ?1",
+                params![amount, user_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if changed == 0 {
+            self.user_balance(user_id)?;
+            return Err("Not enough funds".into());
+        }
+        self.record_ledger(user_id, -amount, kind, skin_id, inventory_id)?;
+        self.user_balance(user_id)
+    }
+
+    /// Add `amount` to a user's balance, record it in the ledger as `kind` with the
+    /// related skin / inventory ids, and return the new balance. Meant to run inside
+    /// the caller's transaction.
+    pub fn credit_balance(
+        &self,
+        user_id: i64,
+        amount: Money,
+        kind: LedgerKind,
+        skin_id: Option<i64>,
+        inventory_id: Option<i64>,
+    ) -> DbResult<Money> {
+        let changed = self.conn
+            .execute("UPDATE users SET balance = balance + ?1 WHERE id = ?2", params![amount, user_id])
+            .map_err(|e| e.to_string())?;
+        if changed == 0 {
+            return Err("User not found".into());
+        }
+        self.record_ledger(user_id, amount, kind, skin_id, inventory_id)?;
+        self.user_balance(user_id)
+    }
+
+    /// Append a ledger entry. `amount` is the signed balance change. Balance changes go
+    /// through `debit_balance` / `credit_balance`, which call this; use it directly only
+    /// for operations that move no money (e.g. trade-ups). Meant to run inside the
+    /// caller's transaction.
+    pub fn record_ledger(
+        &self,
+        user_id: i64,
+        amount: Money,
+        kind: LedgerKind,
+        skin_id: Option<i64>,
+        inventory_id: Option<i64>,
+    ) -> DbResult<i64> {
+        self.conn.execute(
+            "INSERT INTO ledger (user_id, amount, kind, skin_id, inventory_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![user_id, amount, kind, skin_id, inventory_id],
+        )
         .map_err(|e| e.to_string())?;
+        Ok(self.conn.last_insert_rowid())
+    }
 
-    match stmt.query_row(params![name], |row| {
-        Ok(crate::models::Skin {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            rarity: row.get(2)?,
-            price: row.get(3)?,
-            collection: row.get(4)?,
-            weapon_type: row.get(5)?,
-            image_base64: row.get(6)?,
-        })
-    }) {
-        Ok(s) => Ok(Some(s)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
+    /// A user's ledger, newest first, optionally limited to one `kind`.
+    pub fn list_ledger(&self, user_id: i64, kind: Option<LedgerKind>) -> DbResult<Vec<LedgerEntry>> {
+        let mut stmt = self.conn
+            .prepare_cached(
+                "SELECT l.id, l.amount, l.kind, l.skin_id, l.inventory_id, l.created_at, s.name
+                 FROM ledger l
+                 LEFT JOIN skins s ON l.skin_id = s.id
+                 WHERE l.user_id = ?1 AND (?2 IS NULL OR l.kind = ?2)
+                 ORDER BY l.id DESC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map(params![user_id, kind], |row| {
+                Ok(LedgerEntry {
+                    id: row.get(0)?,
+                    amount: row.get(1)?,
+                    kind: row.get(2)?,
+                    skin_id: row.get(3)?,
+                    inventory_id: row.get(4)?,
+                    created_at: row.get(5)?,
+                    skin_name: row.get(6)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut entries = Vec::new();
+        for r in rows {
+            entries.push(r.map_err(|e| e.to_string())?);
+        }
+        Ok(entries)
+    }
+
+    /// Net amount per ledger kind for a user, for kinds that have entries.
+    pub fn ledger_totals(&self, user_id: i64) -> DbResult<Vec<(LedgerKind, Money)>> {
+        let mut stmt = self.conn
+            .prepare_cached("SELECT kind, SUM(amount) FROM ledger WHERE user_id = ?1 GROUP BY kind")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![user_id], |row| Ok((row.get::<_, LedgerKind>(0)?, row.get::<_, Money>(1)?)))
+            .map_err(|e| e.to_string())?;
+
+        let mut totals = Vec::new();
+        for r in rows {
+            totals.push(r.map_err(|e| e.to_string())?);
+        }
+        totals.sort_by_key(|(k, _)| LedgerKind::ALL.iter().position(|x| x == k));
+        Ok(totals)
     }
-}
 
-pub fn list_skins(path: &str) -> DbResult<Vec<crate::models::Skin>> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare("SELECT id, name, rarity, price, collection, weapon_type, image_base64 FROM skins ORDER BY name")
+    /// Add a skin to the canonical catalog. Returns the inserted Skin.
+    /// `image` is the encoded image file (PNG, JPEG, ...), stored in `skin_images`
+    /// unless the skin already has one. `float_range` is the `(min, max)` float the
+    /// skin can be rolled with.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_skin(
+        &self,
+        name: &str,
+        rarity: Option<Rarity>,
+        price: Money,
+        collection: Option<&str>,
+        weapon_type: Option<&str>,
+        image: Option<&[u8]>,
+        float_range: (f64, f64),
+    ) -> DbResult<crate::models::Skin> {
+        let (min_float, max_float) = float_range;
+        if !(0.0..=1.0).contains(&min_float) || !(0.0..=1.0).contains(&max_float) || min_float > max_float {
+            return Err(format!("invalid float range {}–{} for '{}'", min_float, max_float, name));
+        }
+
+        self.conn.execute(
+            "INSERT OR IGNORE INTO skins (name, rarity, price, base_price, collection, weapon_type, min_float, max_float) VALUES (?1, ?2, ?3, ?3, ?4, ?5, ?6, ?7)",
+            params![name, rarity, price, collection, weapon_type, min_float, max_float],
+        )
         .map_err(|e| e.to_string())?;
+        if let Some(image) = image {
+            self.conn
+                .execute(
+                    "INSERT OR IGNORE INTO skin_images (skin_id, image) SELECT id, ?2 FROM skins WHERE name = ?1",
+                    params![name, image],
+                )
+                .map_err(|e| e.to_string())?;
+        }
 
-    let rows = stmt
-        .query_map([], |row| {
-            Ok(crate::models::Skin {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                rarity: row.get(2)?,
-                price: row.get(3)?,
-                collection: row.get(4)?,
-                weapon_type: row.get(5)?,
-                image_base64: row.get(6)?,
+        // Return the skin (fetch id)
+        let mut stmt = self.conn
+            .prepare_cached(&format!("SELECT {} FROM skins WHERE name = ?1", SKIN_COLUMNS))
+            .map_err(|e| e.to_string())?;
+
+        let skin = stmt
+            .query_row(params![name], |row| skin_from_row(row, 0))
+            .map_err(|e| e.to_string())?;
+
+        Ok(skin)
+    }
+
+    /// Set a skin's live price and record the change in `price_history`. `kind` is the
+    /// trade that moved it. Meant to run inside that trade's transaction.
+    pub fn set_skin_price(&self, skin_id: i64, price: Money, kind: LedgerKind) -> DbResult<()> {
+        let changed = self.conn
+            .execute("UPDATE skins SET price = ?1 WHERE id = ?2", params![price, skin_id])
+            .map_err(|e| e.to_string())?;
+        if changed == 0 {
+            return Err("Skin not found".into());
+        }
+        self.conn
+            .execute(
+                "INSERT INTO price_history (skin_id, price, kind) VALUES (?1, ?2, ?3)",
+                params![skin_id, price, kind],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// The last `limit` changes of a skin's live price, newest first.
+    pub fn price_history(&self, skin_id: i64, limit: usize) -> DbResult<Vec<PricePoint>> {
+        let mut stmt = self.conn
+            .prepare_cached(
+                "SELECT price, kind, created_at FROM price_history WHERE skin_id = ?1 ORDER BY id DESC LIMIT ?2",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![skin_id, limit as i64], |row| {
+                Ok(PricePoint { price: row.get(0)?, kind: row.get(1)?, created_at: row.get(2)? })
             })
+            .map_err(|e| e.to_string())?;
+
+        let mut points = Vec::new();
+        for r in rows {
+            points.push(r.map_err(|e| e.to_string())?);
+        }
+        Ok(points)
+    }
+
+    /// The stored image file of a skin, if it has one. Catalog queries leave images
+    /// out; screens load them one skin at a time through this.
+    pub fn skin_image(&self, skin_id: i64) -> DbResult<Option<Vec<u8>>> {
+        self.conn
+            .query_row("SELECT image FROM skin_images WHERE skin_id = ?1", params![skin_id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Store or replace a skin's image file.
+    pub fn set_skin_image(&self, skin_id: i64, image: &[u8]) -> DbResult<()> {
+        self.conn
+            .execute("INSERT OR REPLACE INTO skin_images (skin_id, image) VALUES (?1, ?2)", params![skin_id, image])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get_skin_by_name(&self, name: &str) -> DbResult<Option<crate::models::Skin>> {
+        let mut stmt = self.conn
+            .prepare_cached(&format!("SELECT {} FROM skins WHERE name = ?1", SKIN_COLUMNS))
+            .map_err(|e| e.to_string())?;
+
+        match stmt.query_row(params![name], |row| skin_from_row(row, 0)) {
+            Ok(s) => Ok(Some(s)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Look up a catalog skin by id on an open connection or transaction.
+    pub fn find_skin(&self, skin_id: i64) -> DbResult<Option<crate::models::Skin>> {
+        let mut stmt = self.conn
+            .prepare_cached(&format!("SELECT {} FROM skins WHERE id = ?1", SKIN_COLUMNS))
+            .map_err(|e| e.to_string())?;
+
+        match stmt.query_row(params![skin_id], |row| skin_from_row(row, 0)) {
+            Ok(s) => Ok(Some(s)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn list_skins(&self) -> DbResult<Vec<crate::models::Skin>> {
+        let mut stmt = self.conn
+            .prepare_cached(&format!("SELECT {} FROM skins ORDER BY name", SKIN_COLUMNS))
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], |row| skin_from_row(row, 0))
+            .map_err(|e| e.to_string())?;
+
+        let mut skins = Vec::new();
+        for r in rows {
+            skins.push(r.map_err(|e| e.to_string())?);
+        }
+
+        Ok(skins)
+    }
+
+    /// Catalog skins matching `filter`, in its sort order. Filtering and sorting
+    /// run in SQLite, on the indexes added by migration 13.
+    pub fn search_skins(&self, filter: &SkinFilter) -> DbResult<Vec<crate::models::Skin>> {
+        let dir = if filter.descending { "DESC" } else { "ASC" };
+        let order = match filter.sort {
+            SkinSort::Name => format!("name COLLATE NOCASE {}", dir),
+            SkinSort::Price => format!("price {}, name COLLATE NOCASE", dir),
+            SkinSort::Rarity => {
+                let rank: String = Rarity::ALL
+                    .iter()
+                    .enumerate()
+                    .map(|(i, r)| format!(" WHEN '{}' THEN {}", r.as_str(), i))
+                    .collect();
+                // Skins without a rarity go last either way
+                format!("rarity IS NULL, CASE rarity{} END {}, name COLLATE NOCASE", rank, dir)
+            }
+        };
+        let mut stmt = self.conn
+            .prepare_cached(&format!(
+                "SELECT {} FROM skins
+                 WHERE (?1 = '' OR name LIKE '%' || ?1 || '%' ESCAPE '\\')
+                   AND (?2 IS NULL OR rarity = ?2)
+                   AND (?3 IS NULL OR collection = ?3)
+                   AND (?4 IS NULL OR weapon_type = ?4)
+                   AND (?5 IS NULL OR price >= ?5)
+                   AND (?6 IS NULL OR price <= ?6)
+                 ORDER BY {}",
+                SKIN_COLUMNS, order
+            ))
+            .map_err(|e| e.to_string())?;
+
+        // `%` and `_` in the search text match themselves
+        let name = filter.name.trim().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let rows = stmt
+            .query_map(
+                params![name, filter.rarity, filter.collection, filter.weapon_type, filter.min_price, filter.max_price],
+                |row| skin_from_row(row, 0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut skins = Vec::new();
+        for r in rows {
+            skins.push(r.map_err(|e| e.to_string())?);
+        }
+        Ok(skins)
+    }
+
+    /// Collections in the catalog, for the Buy screen's filter menu.
+    pub fn list_collections(&self) -> DbResult<Vec<String>> {
+        self.distinct_skin_values("SELECT DISTINCT collection FROM skins WHERE collection <> '' ORDER BY collection")
+    }
+
+    /// Weapon types in the catalog, for the Buy screen's filter menu.
+    pub fn list_weapon_types(&self) -> DbResult<Vec<String>> {
+        self.distinct_skin_values("SELECT DISTINCT weapon_type FROM skins WHERE weapon_type <> '' ORDER BY weapon_type")
+    }
+
+    fn distinct_skin_values(&self, sql: &str) -> DbResult<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(sql).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], |row| row.get(0)).map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<String>>>().map_err(|e| e.to_string())
+    }
+
+    /// Get all inventory items for a given user.
+    pub fn get_inventory_for_user(&self, user_id: i64) -> DbResult<Vec<crate::models::OwnedSkin>> {
+
+        // Join inventory -> skins so callers get inventory + catalog metadata in one call
+        let mut stmt = self.conn
+            .prepare_cached(&format!(
+                "SELECT {} FROM inventory i LEFT JOIN skins s ON i.skin_id = s.id WHERE i.user_id = ?1",
+                OWNED_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map(params![user_id], owned_from_row).map_err(|e| e.to_string())?;
+
+        let mut items = Vec::new();
+        for r in rows {
+            items.push(r.map_err(|e| e.to_string())?);
+        }
+
+        Ok(items)
+    }
+
+    /// What `user_id`'s inventory is worth at current market prices: totals, the
+    /// breakdown per rarity and per collection, and the `top` most valuable copies.
+    /// Values are summed in SQLite rather than over a loaded inventory.
+    pub fn inventory_value(&self, user_id: i64, top: usize) -> DbResult<InventoryValue> {
+        let value = owned_value_sql();
+        let mut by_rarity: Vec<ValueGroup<Option<Rarity>>> = self.value_groups(user_id, &value, "s.rarity")?;
+        by_rarity.sort_by_key(|g| std::cmp::Reverse(g.key.and_then(|r| Rarity::ALL.iter().position(|x| *x == r))));
+        let by_collection = self.value_groups(user_id, &value, "s.collection")?;
+
+        let mut stmt = self.conn
+            .prepare_cached(&format!(
+                "SELECT {}, {} AS value FROM inventory i LEFT JOIN skins s ON i.skin_id = s.id
+                 WHERE i.user_id = ?1 ORDER BY value DESC, i.id LIMIT ?2",
+                OWNED_COLUMNS, value
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![user_id, top as i64], |row| Ok((owned_from_row(row)?, row.get::<_, Money>("value")?)))
+            .map_err(|e| e.to_string())?;
+        let mut top_items = Vec::new();
+        for r in rows {
+            top_items.push(r.map_err(|e| e.to_string())?);
+        }
+
+        Ok(InventoryValue {
+            count: by_rarity.iter().map(|g| g.count).sum(),
+            total: by_rarity.iter().map(|g| g.value).sum(),
+            by_rarity,
+            by_collection,
+            top_items,
         })
-        .map_err(|e| e.to_string())?;
+    }
 
-    let mut skins = Vec::new();
-    for r in rows {
-        skins.push(r.map_err(|e| e.to_string())?);
+    /// Count and value of a user's copies grouped by `column` of `skins s`, most valuable first.
+    fn value_groups<K: rusqlite::types::FromSql>(&self, user_id: i64, value: &str, column: &str) -> DbResult<Vec<ValueGroup<K>>> {
+        let mut stmt = self.conn
+            .prepare_cached(&format!(
+                "SELECT {col}, COUNT(*), SUM({value}) AS total FROM inventory i LEFT JOIN skins s ON i.skin_id = s.id
+                 WHERE i.user_id = ?1 GROUP BY {col} ORDER BY total DESC, {col}",
+                col = column,
+                value = value
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![user_id], |row| {
+                Ok(ValueGroup { key: row.get(0)?, count: row.get::<_, i64>(1)? as usize, value: row.get(2)? })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut groups = Vec::new();
+        for r in rows {
+            groups.push(r.map_err(|e| e.to_string())?);
+        }
+        Ok(groups)
     }
 
-    Ok(skins)
-}
+    /// Remove an inventory item by id.
+    pub fn remove_inventory_item(&self, item_id: i64) -> DbResult<()> {
+        self.conn.execute("DELETE FROM inventory WHERE id = ?1", params![item_id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Remove inventory item `item_id` from `user_id`'s inventory and return it. Fails
+    /// without deleting anything if the item does not exist or belongs to someone
+    /// else. Meant to run inside the caller's transaction.
+    pub fn take_inventory_item(&self, user_id: i64, item_id: i64) -> DbResult<crate::models::OwnedSkin> {
+        let owned = match self.conn.query_row(
+            &format!(
+                "SELECT {} FROM inventory i LEFT JOIN skins s ON i.skin_id = s.id WHERE i.id = ?1 AND i.user_id = ?2",
+                OWNED_COLUMNS
+            ),
+            params![item_id, user_id],
+            owned_from_row,
+        ) {
+            Ok(o) => o,
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                return Err(format!("You do not own inventory item {}", item_id));
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        self.conn.execute("DELETE FROM inventory WHERE id = ?1 AND user_id = ?2", params![item_id, user_id])
+            .map_err(|e| e.to_string())?;
+        Ok(owned)
+    }
 
-/// Get all inventory items for a given user.
-pub fn get_inventory_for_user(path: &str, user_id: i64) -> DbResult<Vec<crate::models::OwnedSkin>> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-
-    // Join inventory -> skins so callers get inventory + catalog metadata in one call
-    let mut stmt = conn
-        .prepare(
-            "SELECT i.id, i.user_id, i.skin_id,
-                    s.id, s.name, s.rarity, s.price, s.collection, s.weapon_type, s.image_base64
-             FROM inventory i
-             LEFT JOIN skins s ON i.skin_id = s.id
-             WHERE i.user_id = ?1",
+    /// Add a case and the collections it drops from. Re-adding an existing case name
+    /// keeps the stored case and only adds missing collections. Returns the stored Case.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_case(
+        &self,
+        name: &str,
+        price: Money,
+        key_price: Money,
+        image_base64: Option<&str>,
+        collections: &[String],
+        rare_special_collections: &[String],
+        odds: &[(Rarity, f64)],
+    ) -> DbResult<crate::models::Case> {
+        for (rarity, chance) in odds {
+            if !chance.is_finite() || *chance < 0.0 {
+                return Err(format!("invalid odds {} for rarity '{}'", chance, rarity.label()));
+            }
+        }
+
+        let tx = self.transaction()?;
+
+        tx.execute(
+            "INSERT OR IGNORE INTO cases (name, price, key_price, image_base64) VALUES (?1, ?2, ?3, ?4)",
+            params![name, price, key_price, image_base64],
         )
         .map_err(|e| e.to_string())?;
+        let case_id: i64 = tx
+            .query_row("SELECT id FROM cases WHERE name = ?1", params![name], |r| r.get(0))
+            .map_err(|e| e.to_string())?;
 
-    let rows = stmt
-        .query_map(params![user_id], |row| {
-            let inv = crate::models::InventoryItem {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                skin_id: row.get(2)?,
-            };
-
-            // skin fields may be NULL (if skin_id is null or missing)
-            let skin_id: Option<i64> = row.get(3)?;
-            let skin = if let Some(_) = skin_id {
-                Some(crate::models::Skin {
-                    id: row.get(3)?,
-                    name: row.get(4)?,
-                    rarity: row.get(5)?,
-                    price: row.get(6)?,
-                    collection: row.get(7)?,
-                    weapon_type: row.get(8)?,
-                    image_base64: row.get(9)?,
-                })
+        for (list, rare_special) in [(collections, false), (rare_special_collections, true)] {
+            for collection in list {
+                tx.execute(
+                    "INSERT OR IGNORE INTO case_collections (case_id, collection, rare_special) VALUES (?1, ?2, ?3)",
+                    params![case_id, collection, rare_special],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+
+        for (rarity, chance) in odds {
+            tx.execute(
+                "INSERT OR IGNORE INTO case_odds (case_id, rarity, odds) VALUES (?1, ?2, ?3)",
+                params![case_id, rarity, chance],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().map_err(|e| e.to_string())?;
+
+        self.get_case_by_id(case_id)?.ok_or_else(|| format!("case '{}' was not stored", name))
+    }
+
+    /// Load the collections and drop odds of a case into `case`.
+    fn load_case_collections(&self, case: &mut crate::models::Case) -> Result<()> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT collection, rare_special FROM case_collections WHERE case_id = ?1 ORDER BY collection",
+        )?;
+        let rows = stmt.query_map(params![case.id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)))?;
+        for r in rows {
+            let (collection, rare_special) = r?;
+            if rare_special {
+                case.rare_special_collections.push(collection);
             } else {
-                None
-            };
+                case.collections.push(collection);
+            }
+        }
 
-            Ok(crate::models::OwnedSkin { inventory: inv, skin })
-        })
-        .map_err(|e| e.to_string())?;
+        let mut stmt = self.conn.prepare_cached("SELECT rarity, odds FROM case_odds WHERE case_id = ?1")?;
+        let rows = stmt.query_map(params![case.id], |row| Ok((row.get::<_, Rarity>(0)?, row.get::<_, f64>(1)?)))?;
+        for r in rows {
+            case.odds.push(r?);
+        }
+        if case.odds.is_empty() {
+            case.odds = Rarity::DEFAULT_CASE_ODDS.to_vec();
+        }
+        Ok(())
+    }
+
+    /// Get a case (with its collections) by id
+    pub fn get_case_by_id(&self, case_id: i64) -> DbResult<Option<crate::models::Case>> {
+        let mut stmt = self.conn
+            .prepare_cached("SELECT id, name, price, key_price, image_base64 FROM cases WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
 
-    let mut items = Vec::new();
-    for r in rows {
-        items.push(r.map_err(|e| e.to_string())?);
+        let mut case = match stmt.query_row(params![case_id], case_from_row) {
+            Ok(c) => c,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        self.load_case_collections(&mut case).map_err(|e| e.to_string())?;
+        Ok(Some(case))
     }
 
-    Ok(items)
+    /// List all cases (with their collections), ordered by name.
+    pub fn list_cases(&self) -> DbResult<Vec<crate::models::Case>> {
+        let mut stmt = self.conn
+            .prepare_cached("SELECT id, name, price, key_price, image_base64 FROM cases ORDER BY name")
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt.query_map([], case_from_row).map_err(|e| e.to_string())?;
+
+        let mut cases = Vec::new();
+        for r in rows {
+            let mut case = r.map_err(|e| e.to_string())?;
+            self.load_case_collections(&mut case).map_err(|e| e.to_string())?;
+            cases.push(case);
+        }
+
+        Ok(cases)
+    }
 }
 
-/// Update the quantity of an inventory item. Returns Ok(()) on success.
-// Removed update_inventory_quantity - inventory no longer tracks a quantity field.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::Database;
 
-/// Remove an inventory item by id.
-pub fn remove_inventory_item(path: &str, item_id: i64) -> DbResult<()> {
-    let conn = Connection::open(path).map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM inventory WHERE id = ?1", params![item_id])
-        .map_err(|e| e.to_string())?;
-    Ok(())
+    /// Create a fresh, initialized database file in the temp dir and open it.
+    pub fn temp_db(name: &str) -> Database {
+        let path = std::env::temp_dir().join(format!("cs_trade_up_test_{}_{}.db", name, std::process::id()));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        Database::open(&path.to_string_lossy()).expect("init test db")
+    }
+
+    /// Add a user and a 10.00 Mil-Spec skin; returns `(user_id, skin_id)`.
+    pub fn user_and_skin(db: &Database) -> (i64, i64) {
+        let user = db.create_user(&crate::accounts::AccountRules::default(), "tester", "secret12").expect("create user");
+        let skin = db
+            .add_skin(
+                "Test | Skin",
+                Some(crate::models::Rarity::MilSpec),
+                crate::models::Money::from_cents(1000),
+                Some("Test Collection"),
+                Some("Rifle"),
+                None,
+                (0.0, 1.0),
+            )
+            .expect("add skin");
+        (user.id, skin.id)
+    }
+
+    pub fn balance(db: &Database, user_id: i64) -> crate::models::Money {
+        db.get_user_by_id(user_id).unwrap().unwrap().balance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn real_money_columns_migrate_to_cents() {
+        let path = std::env::temp_dir().join(format!("cs_trade_up_test_real_money_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_string_lossy().into_owned();
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, username TEXT NOT NULL UNIQUE, password TEXT NOT NULL, balance REAL DEFAULT 100.0);
+                 CREATE TABLE skins (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, rarity TEXT, price REAL DEFAULT 0.0,
+                                     collection TEXT, weapon_type TEXT, image_base64 TEXT);
+                 INSERT INTO users (username, password, balance) VALUES ('old', 'pw', 0.1 + 0.2);
+                 INSERT INTO skins (name, rarity, price) VALUES ('Old | Skin', 'Mil-Spec Grade', 12.34);",
+            )
+            .unwrap();
+        }
+
+        let db = Database::open(&path).unwrap();
+
+        let rules = AccountRules::default();
+        let user = db.authenticate(&rules, "old", "pw").unwrap();
+        assert_eq!(user.balance, Money::from_cents(30));
+
+        // The plaintext password was rehashed on login and still works
+        let stored: String = db.conn.query_row("SELECT password_hash FROM users", [], |r| r.get(0)).unwrap();
+        assert!(crate::password::is_hashed(&stored));
+        assert!(db.authenticate(&rules, "old", "pw").is_ok());
+        assert_eq!(db.authenticate(&rules, "old", "wrong").unwrap_err(), AuthError::InvalidCredentials);
+        assert_eq!(db.ledger_totals(user.id).unwrap(), [(LedgerKind::Admin, Money::from_cents(30))]);
+        let skin = db.get_skin_by_name("Old | Skin").unwrap().unwrap();
+        assert_eq!(skin.price, Money::from_cents(1234));
+        assert_eq!(skin.rarity, Some(Rarity::MilSpec));
+
+        // Running the migration again leaves the converted values alone
+        drop(db);
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.get_skin_by_name("Old | Skin").unwrap().unwrap().price, Money::from_cents(1234));
+    }
+
+    #[test]
+    fn change_password_requires_the_old_one() {
+        let db = test_support::temp_db("change_password");
+        let (user_id, _) = test_support::user_and_skin(&db);
+        let rules = AccountRules::default();
+
+        assert_eq!(db.change_password(&rules, user_id, "wrong", "newpass12"), Err(AuthError::IncorrectPassword));
+        assert_eq!(db.change_password(&rules, user_id, "secret12", "short"), Err(AuthError::PasswordTooShort { min: 8 }));
+        assert!(db.authenticate(&rules, "tester", "secret12").is_ok());
+
+        db.change_password(&rules, user_id, "secret12", "newpass12").unwrap();
+        assert_eq!(db.authenticate(&rules, "tester", "secret12").unwrap_err(), AuthError::InvalidCredentials);
+        assert!(db.authenticate(&rules, "tester", "newpass12").is_ok());
+    }
+
+    #[test]
+    fn unknown_username_is_rejected_like_a_wrong_password() {
+        let db = test_support::temp_db("unknown_username");
+        test_support::user_and_skin(&db);
+        let rules = AccountRules::default();
+
+        assert_eq!(db.authenticate(&rules, "nobody", "secret12").unwrap_err(), AuthError::InvalidCredentials);
+        assert_eq!(db.authenticate(&rules, "tester", "wrong").unwrap_err(), AuthError::InvalidCredentials);
+    }
+
+    #[test]
+    fn rename_checks_rules_and_uniqueness() {
+        let db = test_support::temp_db("rename_user");
+        let (user_id, _) = test_support::user_and_skin(&db);
+        let rules = AccountRules::default();
+        db.create_user(&rules, "other", "password1").unwrap();
+
+        assert_eq!(db.rename_user(&rules, user_id, "other").unwrap_err(), AuthError::UsernameTaken);
+        assert_eq!(db.rename_user(&rules, user_id, "OTHER").unwrap_err(), AuthError::UsernameTaken);
+        assert_eq!(db.rename_user(&rules, user_id, "no spaces").unwrap_err(), AuthError::UsernameChars { symbols: "_-." });
+        // Keeping your own name (or changing its case) is not a conflict
+        db.rename_user(&rules, user_id, "Tester").unwrap();
+
+        let user = db.rename_user(&rules, user_id, "renamed").unwrap();
+        assert_eq!(user.username, "renamed");
+        assert!(db.authenticate(&rules, "renamed", "secret12").is_ok());
+        assert!(db.authenticate(&rules, "tester", "secret12").is_err());
+    }
+
+    #[test]
+    fn delete_user_cascades() {
+        let db = test_support::temp_db("delete_user");
+        let (user_id, skin_id) = test_support::user_and_skin(&db);
+        crate::scripts::buy::attempt_buy(&db, &mut Default::default(), &crate::scripts::market::MarketConfig::FIXED, user_id, skin_id, crate::models::Variant::Normal).unwrap();
+
+        assert_eq!(db.delete_user(user_id, "wrong"), Err(AuthError::IncorrectPassword));
+        assert_eq!(db.get_inventory_for_user(user_id).unwrap().len(), 1);
+
+        db.delete_user(user_id, "secret12").unwrap();
+        assert!(db.get_user_by_id(user_id).unwrap().is_none());
+        assert!(db.get_inventory_for_user(user_id).unwrap().is_empty());
+        assert!(db.list_ledger(user_id, None).unwrap().is_empty());
+        // The name is free again
+        db.create_user(&AccountRules::default(), "tester", "secret12").unwrap();
+    }
+
+    #[test]
+    fn sessions_expire_and_die_with_the_account() {
+        let db = test_support::temp_db("sessions");
+        let (user_id, _) = test_support::user_and_skin(&db);
+
+        let expired = db.create_session(user_id, -1).unwrap();
+        assert!(db.session_user(&expired).unwrap().is_none());
+
+        let token = db.create_session(user_id, 60).unwrap();
+        assert_eq!(db.session_user(&token).unwrap().unwrap().id, user_id);
+        assert!(db.session_user("not a token").unwrap().is_none());
+
+        db.delete_user(user_id, "secret12").unwrap();
+        assert!(db.session_user(&token).unwrap().is_none());
+    }
+
+    #[test]
+    fn registration_enforces_rules() {
+        let db = test_support::temp_db("registration_rules");
+        let rules = AccountRules::default();
+
+        assert_eq!(db.create_user(&rules, "", "password1").unwrap_err(), AuthError::UsernameLength { min: 3, max: 20 });
+        assert_eq!(db.create_user(&rules, "someone", "").unwrap_err(), AuthError::PasswordTooShort { min: 8 });
+        db.create_user(&rules, "Someone", "password1").unwrap();
+        assert_eq!(db.create_user(&rules, "someone", "password1").unwrap_err(), AuthError::UsernameTaken);
+        // Login ignores case too
+        assert_eq!(db.authenticate(&rules, "SOMEONE", "password1").unwrap().username, "Someone");
+    }
+
+    #[test]
+    fn repeated_failures_lock_the_account() {
+        let db = test_support::temp_db("lockout");
+        test_support::user_and_skin(&db);
+        let rules = AccountRules { max_failed_logins: 3, failure_window_secs: 60, lockout_secs: 300, ..Default::default() };
+        let t = 1_000_000;
+
+        // Failures spread wider than the window never add up to a lock
+        for i in 0..4 {
+            let err = db.authenticate_at(&rules, "tester", "wrong", t + i * 61).unwrap_err();
+            assert_eq!(err, AuthError::InvalidCredentials);
+        }
+
+        let t = t + 1000;
+        for i in 0..3 {
+            assert_eq!(db.authenticate_at(&rules, "tester", "wrong", t + i).unwrap_err(), AuthError::InvalidCredentials);
+        }
+        // Locked, even with the right password, until the lockout runs out
+        assert_eq!(
+            db.authenticate_at(&rules, "TESTER", "secret12", t + 2 + 100).unwrap_err(),
+            AuthError::LockedOut { retry_after_secs: 200 }
+        );
+
+        // The counter survives a fresh connection (it lives in SQLite)
+        let path = db.path().to_string();
+        drop(db);
+        let db = Database::open(&path).unwrap();
+        assert!(db.authenticate_at(&rules, "tester", "secret12", t + 2 + 299).is_err());
+        assert!(db.authenticate_at(&rules, "tester", "secret12", t + 2 + 300).is_ok());
+        // A successful login clears the counter
+        assert_eq!(db.authenticate_at(&rules, "tester", "wrong", t + 400).unwrap_err(), AuthError::InvalidCredentials);
+        assert!(db.authenticate_at(&rules, "tester", "secret12", t + 401).is_ok());
+    }
+
+    #[test]
+    fn skin_images_are_stored_apart_and_loaded_by_id() {
+        let db = test_support::temp_db("images");
+        let (_, plain) = test_support::user_and_skin(&db);
+        let skin = db.add_skin("Pictured", None, Money::ZERO, None, None, Some(&[1, 2, 3]), (0.0, 1.0)).unwrap();
+        assert!(skin.has_image);
+        assert!(!db.find_skin(plain).unwrap().unwrap().has_image);
+        assert_eq!(db.skin_image(skin.id).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(db.skin_image(plain).unwrap(), None);
+
+        // Seed entries take an image file path (plain or file://) or base64 text
+        let dir = std::env::temp_dir().join(format!("cs_trade_up_test_seed_images_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("img")).unwrap();
+        std::fs::write(dir.join("img/a.png"), [7, 8]).unwrap();
+        let seed = |json: &str| serde_json::from_str::<SeedSkin>(json).unwrap().load_image(&dir);
+        assert_eq!(seed(r#"{"name": "A", "image_path": "img/a.png"}"#), Some(vec![7, 8]));
+        let url = format!(r#"{{"name": "A", "image_path": "file://{}"}}"#, dir.join("img/a.png").display());
+        assert_eq!(seed(&url), Some(vec![7, 8]));
+        assert_eq!(seed(r#"{"name": "B", "image_base64": "data:image/png;base64,AAEC"}"#), Some(vec![0, 1, 2]));
+        assert_eq!(seed(r#"{"name": "C", "image_path": "img/missing.png"}"#), None);
+    }
+
+    #[test]
+    fn search_filters_and_sorts_in_sql() {
+        let db = test_support::temp_db("search");
+        // Start from an empty catalog; opening the database seeds it from data/
+        db.conn.execute_batch("DELETE FROM skins").unwrap();
+        let add = |name: &str, rarity, cents, collection, weapon| {
+            db.add_skin(name, rarity, Money::from_cents(cents), Some(collection), Some(weapon), None, (0.0, 1.0)).unwrap();
+        };
+        add("AK-47 | Redline", Some(Rarity::Classified), 1250, "Phoenix", "Rifle");
+        add("AWP | Asiimov", Some(Rarity::Covert), 9000, "Phoenix", "Sniper Rifle");
+        add("P250 | Sand Dune", Some(Rarity::Consumer), 3, "Dust", "Pistol");
+        add("M4A4 | 100%_Real", Some(Rarity::MilSpec), 500, "Dust", "Rifle");
+        db.add_skin("Mystery", None, Money::from_cents(700), None, None, None, (0.0, 1.0)).unwrap();
+
+        let names = |filter: SkinFilter| -> Vec<String> {
+            db.search_skins(&filter).unwrap().into_iter().map(|s| s.name).collect()
+        };
+        assert_eq!(names(SkinFilter { name: "  a".into(), ..Default::default() }).len(), 4);
+        assert_eq!(names(SkinFilter { name: "%_".into(), ..Default::default() }), ["M4A4 | 100%_Real"]);
+        assert_eq!(names(SkinFilter { name: "REDLINE".into(), ..Default::default() }), ["AK-47 | Redline"]);
+        assert_eq!(
+            names(SkinFilter { collection: Some("Dust".into()), sort: SkinSort::Price, ..Default::default() }),
+            ["P250 | Sand Dune", "M4A4 | 100%_Real"]
+        );
+        assert_eq!(
+            names(SkinFilter { weapon_type: Some("Rifle".into()), min_price: Some(Money::from_cents(500)), max_price: Some(Money::from_cents(1250)), ..Default::default() }),
+            ["AK-47 | Redline", "M4A4 | 100%_Real"]
+        );
+        assert_eq!(names(SkinFilter { rarity: Some(Rarity::Covert), ..Default::default() }), ["AWP | Asiimov"]);
+        // Rarity sorts by tier, not alphabetically; skins without one come last
+        assert_eq!(
+            names(SkinFilter { sort: SkinSort::Rarity, descending: true, ..Default::default() }),
+            ["AWP | Asiimov", "AK-47 | Redline", "M4A4 | 100%_Real", "P250 | Sand Dune", "Mystery"]
+        );
+
+        assert_eq!(db.list_collections().unwrap(), ["Dust", "Phoenix"]);
+        assert_eq!(db.list_weapon_types().unwrap(), ["Pistol", "Rifle", "Sniper Rifle"]);
+    }
+
+    #[test]
+    fn connection_uses_wal_and_foreign_keys() {
+        let db = test_support::temp_db("pragmas");
+        let mode: String = db.conn.query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+        let fks: bool = db.conn.query_row("PRAGMA foreign_keys", [], |r| r.get(0)).unwrap();
+        assert!(fks);
+
+        // Inventory rows must point at a real user and skin
+        let (user_id, skin_id) = test_support::user_and_skin(&db);
+        assert!(db.add_inventory_item(9999, skin_id, 0.2, Variant::Normal).is_err());
+        assert!(db.add_inventory_item(user_id, 9999, 0.2, Variant::Normal).is_err());
+
+        // A dropped transaction rolls back everything done through `db` inside it
+        {
+            let _tx = db.transaction().unwrap();
+            db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap();
+        }
+        assert!(db.get_inventory_for_user(user_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn inventory_value_is_aggregated_in_sql() {
+        let db = test_support::temp_db("inventory_value");
+        let (user_id, skin_id) = test_support::user_and_skin(&db);
+        let covert = db.add_skin("Test | Covert", Some(Rarity::Covert), Money::from_cents(10000), Some("Other"), None, None, (0.0, 1.0)).unwrap().id;
+        let odd = db.add_skin("Test | Odd", None, Money::from_cents(333), Some("Other"), None, None, (0.0, 1.0)).unwrap().id;
+        db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap();
+        db.add_inventory_item(user_id, skin_id, 0.01, Variant::Normal).unwrap();
+        let best = db.add_inventory_item(user_id, covert, 0.01, Variant::StatTrak).unwrap().id;
+        db.add_inventory_item(user_id, odd, 0.4, Variant::Souvenir).unwrap();
+        let other = db.create_user(&Default::default(), "other", "secret12").unwrap().id;
+        db.add_inventory_item(other, covert, 0.01, Variant::Normal).unwrap();
+
+        let value = db.inventory_value(user_id, 2).unwrap();
+        assert_eq!(value.count, 4);
+        // $10 FT + $15 FN + $300 FN StatTrak™ + round($3.33 * 0.85 * 1.6)
+        assert_eq!(value.total, Money::from_cents(1000 + 1500 + 30000 + 453));
+        let items = db.get_inventory_for_user(user_id).unwrap();
+        assert_eq!(value.total, items.iter().map(crate::scripts::sell::item_value).sum());
+
+        let rarities: Vec<(Option<Rarity>, usize, Money)> = value.by_rarity.iter().map(|g| (g.key, g.count, g.value)).collect();
+        assert_eq!(
+            rarities,
+            [
+                (Some(Rarity::Covert), 1, Money::from_cents(30000)),
+                (Some(Rarity::MilSpec), 2, Money::from_cents(2500)),
+                (None, 1, Money::from_cents(453)),
+            ]
+        );
+        let collections: Vec<(Option<&str>, usize)> = value.by_collection.iter().map(|g| (g.key.as_deref(), g.count)).collect();
+        assert_eq!(collections, [(Some("Other"), 2), (Some("Test Collection"), 2)]);
+        let top: Vec<(i64, Money)> = value.top_items.iter().map(|(it, v)| (it.inventory.id, *v)).collect();
+        assert_eq!(top[0], (best, Money::from_cents(30000)));
+        assert_eq!(top.len(), 2);
+
+        let empty = db.inventory_value(db.create_user(&Default::default(), "empty", "secret12").unwrap().id, 5).unwrap();
+        assert_eq!((empty.count, empty.total), (0, Money::ZERO));
+        assert!(empty.by_rarity.is_empty() && empty.top_items.is_empty());
+    }
+
+    #[test]
+    fn inventory_rows_keep_their_float_and_variant() {
+        let db = test_support::temp_db("inventory_round_trip");
+        let (user_id, skin_id) = test_support::user_and_skin(&db);
+        let added = db.add_inventory_item(user_id, skin_id, 0.123456789, Variant::StatTrak).unwrap();
+        assert_eq!(added.wear, Wear::MinimalWear);
+
+        let items = db.get_inventory_for_user(user_id).unwrap();
+        let item = &items[0].inventory;
+        assert_eq!(item.id, added.id);
+        assert_eq!(item.float_value, 0.123456789);
+        assert_eq!((item.wear, item.variant), (Wear::MinimalWear, Variant::StatTrak));
+    }
+
+    #[test]
+    fn seed_rarities_must_be_known() {
+        assert_eq!(parse_seed_rarity("AK-47 | Redline", "Classified"), Ok(Rarity::Classified));
+        assert_eq!(
+            parse_seed_rarity("AK-47 | Redline", "uncommon").unwrap_err(),
+            "unknown rarity 'uncommon' for 'AK-47 | Redline' (expected one of: consumer, industrial, mil-spec, restricted, classified, covert, rare special)"
+        );
+        assert!(parse_seed_rarity("AK-47 | Redline", "not covert").is_err());
+    }
 }
@@ -0,0 +1,665 @@
+//! Numbered schema migrations. The schema version is kept in `PRAGMA user_version`;
+//! `migrate` applies every migration above it in order, each in its own
+//! transaction together with the version bump, so a failed step leaves the
+//! database at the previous version.
+//!
+//! Databases created before versioning report version 0 whatever their shape
+//! (the original `skin_name` inventory, the first catalog schema, or anything
+//! the ad-hoc checks in `init_db` produced since). Migrations therefore check
+//! for existing tables and columns instead of assuming the previous version's
+//! exact layout. New migrations go at the end of `MIGRATIONS` and are never
+//! edited once released.
+
+use rusqlite::{params, Connection, Result, Transaction};
+
+struct Migration {
+    version: i32,
+    name: &'static str,
+    apply: fn(&Transaction) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "initial schema", apply: initial_schema },
+    Migration { version: 2, name: "float values and wear", apply: float_and_wear },
+    Migration { version: 3, name: "StatTrak and Souvenir variants", apply: variants },
+    Migration { version: 4, name: "cases", apply: cases },
+    Migration { version: 5, name: "per-case drop odds", apply: case_odds },
+    Migration { version: 6, name: "canonical rarity names", apply: canonical_rarities },
+    Migration { version: 7, name: "money in integer cents", apply: money_in_cents },
+    Migration { version: 8, name: "ledger", apply: ledger },
+    Migration { version: 9, name: "hashed passwords", apply: password_hash },
+    Migration { version: 10, name: "failed login counter", apply: login_failures },
+    Migration { version: 11, name: "remember-me sessions", apply: sessions },
+    Migration { version: 12, name: "skin images as blobs", apply: skin_images },
+    Migration { version: 13, name: "catalog search indexes", apply: catalog_indexes },
+    Migration { version: 14, name: "market prices", apply: market_prices },
+];
+
+/// Schema version of a fully migrated database.
+pub const LATEST_VERSION: i32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Bring the database up to `LATEST_VERSION`. Fails without changing anything if
+/// the database was written by a newer version of the app.
+pub fn migrate(conn: &mut Connection) -> Result<()> {
+    migrate_to(conn, LATEST_VERSION)
+}
+
+/// Apply migrations up to and including `target`.
+fn migrate_to(conn: &mut Connection, target: i32) -> Result<()> {
+    let current = schema_version(conn)?;
+    if current > LATEST_VERSION {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+            Some(format!(
+                "database schema version {} is newer than this app supports ({})",
+                current, LATEST_VERSION
+            )),
+        ));
+    }
+
+    for m in MIGRATIONS.iter().filter(|m| m.version > current && m.version <= target) {
+        let tx = conn.transaction()?;
+        (m.apply)(&tx).map_err(|e| annotate(e, m))?;
+        tx.pragma_update(None, "user_version", m.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+pub fn schema_version(conn: &Connection) -> Result<i32> {
+    conn.query_row("PRAGMA user_version", [], |r| r.get(0))
+}
+
+/// Name the failing migration in SQLite errors.
+fn annotate(e: rusqlite::Error, m: &Migration) -> rusqlite::Error {
+    match e {
+        rusqlite::Error::SqliteFailure(code, msg) => rusqlite::Error::SqliteFailure(
+            code,
+            Some(format!("migration {} ({}): {}", m.version, m.name, msg.unwrap_or_default())),
+        ),
+        other => other,
+    }
+}
+
+/// Whether `table` has a column named `column`.
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut info_stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = info_stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(1)?;
+        if name == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Add `column` to `table` if an older DB is missing it. Returns whether the column was added.
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool> {
+    if has_column(conn, table, column)? {
+        return Ok(false);
+    }
+
+    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    Ok(true)
+}
+
+/// 1: users, the skin catalog and inventory rows pointing at catalog skins.
+///
+/// The very first schema stored inventory by `skin_name` with a `rarity`, `price`
+/// and `quantity` per row and had no catalog. Such rows are turned into catalog
+/// skins plus one inventory row per copy.
+fn initial_schema(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            password TEXT NOT NULL,
+            balance REAL DEFAULT 100.0
+        );
+        CREATE TABLE IF NOT EXISTS skins (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            rarity TEXT,
+            price REAL DEFAULT 0.0,
+            collection TEXT,
+            weapon_type TEXT,
+            image_base64 TEXT
+        );
+        CREATE TABLE IF NOT EXISTS inventory (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            skin_id INTEGER NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES users(id),
+            FOREIGN KEY(skin_id) REFERENCES skins(id)
+        );",
+    )?;
+
+    // Accounts from before balances start with the old default of 100.00.
+    if ensure_column(tx, "users", "balance", "REAL DEFAULT 100.0")? {
+        tx.execute("UPDATE users SET balance = 100.0 WHERE balance IS NULL", [])?;
+    }
+
+    if has_column(tx, "inventory", "skin_name")? {
+        tx.execute_batch(
+            "INSERT OR IGNORE INTO skins (name, rarity, price)
+                SELECT skin_name, MAX(rarity), MAX(COALESCE(price, 0.0)) FROM inventory GROUP BY skin_name;
+            CREATE TABLE inventory_by_id (
+                id INTEGER PRIMARY KEY,
+                user_id INTEGER NOT NULL,
+                skin_id INTEGER NOT NULL,
+                FOREIGN KEY(user_id) REFERENCES users(id),
+                FOREIGN KEY(skin_id) REFERENCES skins(id)
+            );
+            WITH RECURSIVE copies(row_id, n) AS (
+                SELECT id, 1 FROM inventory WHERE quantity >= 1
+                UNION ALL
+                SELECT row_id, n + 1 FROM copies JOIN inventory i ON i.id = copies.row_id WHERE n < i.quantity
+            )
+            INSERT INTO inventory_by_id (user_id, skin_id)
+                SELECT i.user_id, s.id FROM copies c
+                JOIN inventory i ON i.id = c.row_id
+                JOIN skins s ON s.name = i.skin_name
+                ORDER BY i.id, c.n;
+            DROP TABLE inventory;
+            ALTER TABLE inventory_by_id RENAME TO inventory;",
+        )?;
+    }
+    Ok(())
+}
+
+/// 2: float ranges on the catalog and float/wear on owned copies. Skins without
+/// a range get the full 0.0–1.0 span; existing inventory rows are placed at the
+/// midpoint of their skin's range.
+fn float_and_wear(tx: &Transaction) -> Result<()> {
+    ensure_column(tx, "skins", "min_float", "REAL DEFAULT 0.0")?;
+    ensure_column(tx, "skins", "max_float", "REAL DEFAULT 1.0")?;
+    ensure_column(tx, "inventory", "float_value", "REAL")?;
+    ensure_column(tx, "inventory", "wear", "TEXT")?;
+    tx.execute(
+        "UPDATE inventory SET float_value = COALESCE(
+            (SELECT (COALESCE(s.min_float, 0.0) + COALESCE(s.max_float, 1.0)) / 2.0
+             FROM skins s WHERE s.id = inventory.skin_id),
+            0.5)
+         WHERE float_value IS NULL",
+        [],
+    )?;
+    tx.execute(
+        "UPDATE inventory SET wear = CASE
+            WHEN float_value < 0.07 THEN 'Factory New'
+            WHEN float_value < 0.15 THEN 'Minimal Wear'
+            WHEN float_value < 0.38 THEN 'Field-Tested'
+            WHEN float_value < 0.45 THEN 'Well-Worn'
+            ELSE 'Battle-Scarred'
+         END
+         WHERE wear IS NULL",
+        [],
+    )?;
+    Ok(())
+}
+
+/// 3: StatTrak™ / Souvenir copies. Existing copies are normal.
+fn variants(tx: &Transaction) -> Result<()> {
+    ensure_column(tx, "inventory", "variant", "TEXT NOT NULL DEFAULT 'Normal'")?;
+    Ok(())
+}
+
+/// 4: cases and the collections they drop from. `rare_special` marks rows that
+/// contribute a collection's rare special skins (knives, gloves) instead of its
+/// regular skins.
+fn cases(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS cases (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            price REAL DEFAULT 0.0,
+            key_price REAL DEFAULT 0.0,
+            image_base64 TEXT
+        );
+        CREATE TABLE IF NOT EXISTS case_collections (
+            case_id INTEGER NOT NULL,
+            collection TEXT NOT NULL,
+            rare_special INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY(case_id, collection, rare_special),
+            FOREIGN KEY(case_id) REFERENCES cases(id)
+        );",
+    )
+}
+
+/// 5: per-case drop odds by canonical rarity. Cases without rows use the default
+/// CS odds (`Rarity::DEFAULT_CASE_ODDS`).
+fn case_odds(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS case_odds (
+            case_id INTEGER NOT NULL,
+            rarity TEXT NOT NULL,
+            odds REAL NOT NULL,
+            PRIMARY KEY(case_id, rarity),
+            FOREIGN KEY(case_id) REFERENCES cases(id)
+        );",
+    )
+}
+
+/// 6: older rows may spell rarities differently ("Mil-Spec Grade", "milspec");
+/// store them under their canonical name. Unknown spellings are left alone.
+fn canonical_rarities(tx: &Transaction) -> Result<()> {
+    let legacy: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT DISTINCT rarity FROM skins WHERE rarity IS NOT NULL")?;
+        let rows = stmt.query_map([], |r| r.get::<_, String>(0))?;
+        rows.collect::<Result<_>>()?
+    };
+    for old in legacy {
+        if let Some(r) = crate::models::Rarity::parse(&old)
+            && r.as_str() != old
+        {
+            tx.execute("UPDATE skins SET rarity = ?1 WHERE rarity = ?2", params![r, old])?;
+        }
+    }
+    Ok(())
+}
+
+/// 7: money used to be REAL dollars; store it as integer cents.
+fn money_in_cents(tx: &Transaction) -> Result<()> {
+    to_cents(tx, "users", "balance", "INTEGER NOT NULL DEFAULT 10000")?;
+    to_cents(tx, "skins", "price", "INTEGER NOT NULL DEFAULT 0")?;
+    to_cents(tx, "cases", "price", "INTEGER NOT NULL DEFAULT 0")?;
+    to_cents(tx, "cases", "key_price", "INTEGER NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+/// Convert a money column holding REAL dollars into INTEGER cents. Each value is
+/// rounded to the nearest cent, so amounts like 12.34 (stored as 12.3399999...)
+/// come out exact. Does nothing if the column is already INTEGER.
+fn to_cents(tx: &Transaction, table: &str, column: &str, definition: &str) -> Result<()> {
+    let declared: Option<String> = tx
+        .query_row(
+            &format!("SELECT type FROM pragma_table_info('{}') WHERE name = ?1", table),
+            params![column],
+            |r| r.get(0),
+        )
+        .ok();
+    if !declared.is_some_and(|t| t.eq_ignore_ascii_case("REAL")) {
+        return Ok(());
+    }
+
+    tx.execute_batch(&format!(
+        "ALTER TABLE {t} ADD COLUMN {c}_cents {d};
+         UPDATE {t} SET {c}_cents = CAST(ROUND(COALESCE({c}, 0) * 100) AS INTEGER);
+         ALTER TABLE {t} DROP COLUMN {c};
+         ALTER TABLE {t} RENAME COLUMN {c}_cents TO {c};",
+        t = table,
+        c = column,
+        d = definition
+    ))
+}
+
+/// 8: ledger, one row per balance change, written in the same transaction as the
+/// change itself. `amount` is signed integer cents. Accounts that predate the
+/// ledger get an opening entry so that every user's ledger sums to their balance.
+fn ledger(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS ledger (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            amount INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            skin_id INTEGER,
+            inventory_id INTEGER,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+        CREATE INDEX IF NOT EXISTS ledger_user ON ledger(user_id, id);
+        INSERT INTO ledger (user_id, amount, kind)
+            SELECT id, balance, 'admin' FROM users
+            WHERE balance <> 0 AND NOT EXISTS (SELECT 1 FROM ledger l WHERE l.user_id = users.id);",
+    )
+}
+
+/// 9: the `password` column held plain text. It becomes `password_hash`; the
+/// old values are rehashed on each user's next login.
+fn password_hash(tx: &Transaction) -> Result<()> {
+    if has_column(tx, "users", "password")? && !has_column(tx, "users", "password_hash")? {
+        tx.execute("ALTER TABLE users RENAME COLUMN password TO password_hash", [])?;
+    }
+    Ok(())
+}
+
+/// 10: failed logins per username (any case) for the lockout in `authenticate`.
+/// Times are Unix seconds.
+fn login_failures(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS login_failures (
+            username TEXT PRIMARY KEY COLLATE NOCASE,
+            failures INTEGER NOT NULL DEFAULT 0,
+            window_start INTEGER NOT NULL,
+            locked_until INTEGER
+        );",
+    )
+}
+
+/// 11: "remember me" logins (see `session.rs`). Times are Unix seconds.
+fn sessions(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sessions (
+            token TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES users(id)
+        );",
+    )
+}
+
+/// 12: skin images were base64 text in `skins.image_base64`, read by every catalog
+/// query. They move to `skin_images` as the raw image file, loaded one skin at a
+/// time; values that are not valid base64 are dropped.
+fn skin_images(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS skin_images (
+            skin_id INTEGER PRIMARY KEY,
+            image BLOB NOT NULL,
+            FOREIGN KEY(skin_id) REFERENCES skins(id) ON DELETE CASCADE
+        );",
+    )?;
+    if !has_column(tx, "skins", "image_base64")? {
+        return Ok(());
+    }
+
+    let mut stmt = tx.prepare("SELECT id, image_base64 FROM skins WHERE image_base64 IS NOT NULL")?;
+    let images = stmt
+        .query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+    drop(stmt);
+    for (skin_id, text) in images {
+        if let Some(image) = super::decode_image_base64(&text) {
+            tx.execute("INSERT OR IGNORE INTO skin_images (skin_id, image) VALUES (?1, ?2)", params![skin_id, image])?;
+        }
+    }
+    tx.execute("ALTER TABLE skins DROP COLUMN image_base64", [])?;
+    Ok(())
+}
+
+/// 13: indexes for the Buy screen's catalog filters and sorts.
+fn catalog_indexes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS skins_rarity ON skins(rarity);
+         CREATE INDEX IF NOT EXISTS skins_collection ON skins(collection);
+         CREATE INDEX IF NOT EXISTS skins_weapon_type ON skins(weapon_type);
+         CREATE INDEX IF NOT EXISTS skins_price ON skins(price);",
+    )
+}
+
+/// 14: simulated market. `skins.price` becomes the live price that buys and sells
+/// move; `base_price` keeps the catalog price it reverts toward, starting at the
+/// current price. `price_history` records every change of the live price.
+fn market_prices(tx: &Transaction) -> Result<()> {
+    if ensure_column(tx, "skins", "base_price", "INTEGER")? {
+        tx.execute("UPDATE skins SET base_price = price", [])?;
+    }
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS price_history (
+            id INTEGER PRIMARY KEY,
+            skin_id INTEGER NOT NULL,
+            price INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY(skin_id) REFERENCES skins(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS price_history_skin ON price_history(skin_id, id);",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every table with its columns (sorted by name, as added columns land at the
+    /// end) and every index, to compare an upgraded schema with a fresh one.
+    fn schema(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT type, name FROM sqlite_master WHERE name NOT LIKE 'sqlite_%' ORDER BY type, name")
+            .unwrap();
+        let objects: Vec<(String, String)> =
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().map(|r| r.unwrap()).collect();
+
+        let mut out = Vec::new();
+        for (kind, name) in objects {
+            out.push(format!("{} {}", kind, name));
+            if kind == "table" {
+                let mut cols = conn
+                    .prepare(&format!(
+                        "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info('{}') ORDER BY name",
+                        name
+                    ))
+                    .unwrap();
+                let cols = cols
+                    .query_map([], |r| {
+                        Ok(format!(
+                            "  {} {} notnull={} default={:?} pk={}",
+                            r.get::<_, String>(0)?,
+                            r.get::<_, String>(1)?,
+                            r.get::<_, i64>(2)?,
+                            r.get::<_, Option<String>>(3)?,
+                            r.get::<_, i64>(4)?
+                        ))
+                    })
+                    .unwrap();
+                out.extend(cols.map(|c| c.unwrap()));
+            }
+        }
+        out
+    }
+
+    fn fresh_schema() -> Vec<String> {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        schema(&conn)
+    }
+
+    fn scalar<T: rusqlite::types::FromSql>(conn: &Connection, sql: &str) -> T {
+        conn.query_row(sql, [], |r| r.get(0)).unwrap()
+    }
+
+    #[test]
+    fn fresh_database_is_at_the_latest_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+
+        // Migrating again is a no-op
+        let before = schema(&conn);
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema(&conn), before);
+    }
+
+    #[test]
+    fn versions_are_numbered_in_order() {
+        for (i, m) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(m.version, i as i32 + 1, "migration '{}'", m.name);
+        }
+    }
+
+    #[test]
+    fn upgrades_from_every_version() {
+        let latest = fresh_schema();
+
+        for version in 0..=LATEST_VERSION {
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate_to(&mut conn, version).unwrap();
+            assert_eq!(schema_version(&conn).unwrap(), version);
+
+            // Data as the app wrote it at that version (version 0 is an empty file)
+            if version >= 1 {
+                let password_col = if version >= 9 { "password_hash" } else { "password" };
+                let (balance, price) = if version >= 7 { ("12345", "1234") } else { ("123.45", "12.34") };
+                let rarity = if version >= 6 { "mil-spec" } else { "Mil-Spec Grade" };
+                conn.execute_batch(&format!(
+                    "INSERT INTO users (username, {}, balance) VALUES ('old', 'pw', {});
+                     INSERT INTO skins (name, rarity, price) VALUES ('Old | Skin', '{}', {});",
+                    password_col, balance, rarity, price
+                ))
+                .unwrap();
+                if version >= 2 {
+                    conn.execute_batch(
+                        "INSERT INTO inventory (user_id, skin_id, float_value, wear) VALUES (1, 1, 0.2, 'Field-Tested');",
+                    )
+                    .unwrap();
+                } else {
+                    conn.execute_batch("INSERT INTO inventory (user_id, skin_id) VALUES (1, 1);").unwrap();
+                }
+            }
+
+            migrate(&mut conn).unwrap();
+            assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION, "from version {}", version);
+            assert_eq!(schema(&conn), latest, "from version {}", version);
+
+            if version >= 1 {
+                assert_eq!(scalar::<i64>(&conn, "SELECT balance FROM users"), 12345, "from version {}", version);
+                assert_eq!(scalar::<i64>(&conn, "SELECT price FROM skins"), 1234, "from version {}", version);
+                assert_eq!(scalar::<String>(&conn, "SELECT rarity FROM skins"), "mil-spec");
+                assert_eq!(scalar::<String>(&conn, "SELECT password_hash FROM users"), "pw");
+                let expected_float = if version >= 2 { 0.2 } else { 0.5 };
+                assert_eq!(scalar::<f64>(&conn, "SELECT float_value FROM inventory"), expected_float);
+                assert_eq!(scalar::<String>(&conn, "SELECT variant FROM inventory"), "Normal");
+                if version < 8 {
+                    assert_eq!(scalar::<i64>(&conn, "SELECT SUM(amount) FROM ledger"), 12345);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_skin_name_inventory_is_converted() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // Layout of the first version (see src/ui/src/db.rs): no balance, no catalog,
+        // inventory rows named by skin with a quantity
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, username TEXT NOT NULL UNIQUE, password TEXT NOT NULL);
+             CREATE TABLE inventory (
+                id INTEGER PRIMARY KEY,
+                user_id INTEGER NOT NULL,
+                skin_name TEXT NOT NULL,
+                rarity TEXT,
+                price REAL DEFAULT 0.0,
+                quantity INTEGER NOT NULL DEFAULT 1,
+                FOREIGN KEY(user_id) REFERENCES users(id)
+             );
+             INSERT INTO users (username, password) VALUES ('first', 'pw');
+             INSERT INTO inventory (user_id, skin_name, rarity, price, quantity) VALUES (1, 'AK-47 | Redline', 'Classified', 12.5, 2);
+             INSERT INTO inventory (user_id, skin_name, rarity, price) VALUES (1, 'P250 | Sand Dune', 'Consumer Grade', 0.03);
+             INSERT INTO inventory (user_id, skin_name, rarity, price, quantity) VALUES (1, 'Gone | Skin', NULL, NULL, 0);",
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema(&conn), fresh_schema());
+
+        assert_eq!(scalar::<i64>(&conn, "SELECT balance FROM users"), 10000);
+        assert_eq!(scalar::<i64>(&conn, "SELECT SUM(amount) FROM ledger WHERE kind = 'admin'"), 10000);
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.name, s.rarity, s.price, i.float_value, i.wear FROM inventory i
+                 JOIN skins s ON s.id = i.skin_id ORDER BY i.id",
+            )
+            .unwrap();
+        let rows: Vec<(String, String, i64, f64, String)> = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let redline = ("AK-47 | Redline".to_string(), "classified".to_string(), 1250, 0.5, "Battle-Scarred".to_string());
+        assert_eq!(
+            rows,
+            [
+                redline.clone(),
+                redline,
+                ("P250 | Sand Dune".to_string(), "consumer".to_string(), 3, 0.5, "Battle-Scarred".to_string()),
+            ]
+        );
+        // A zero-quantity row leaves an (unowned) catalog entry behind but no copies
+        assert_eq!(scalar::<i64>(&conn, "SELECT COUNT(*) FROM skins"), 3);
+    }
+
+    #[test]
+    fn unversioned_current_schema_is_adopted() {
+        // A database written by `init_db` just before versioning: current layout,
+        // but `user_version` still 0
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO users (username, password_hash, balance) VALUES ('recent', '$argon2id$x', 9000);
+             INSERT INTO ledger (user_id, amount, kind) VALUES (1, 10000, 'deposit'), (1, -1000, 'buy');
+             PRAGMA user_version = 0;",
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+        assert_eq!(schema(&conn), fresh_schema());
+        assert_eq!(scalar::<i64>(&conn, "SELECT balance FROM users"), 9000);
+        // No extra opening entry for an account that already has a ledger
+        assert_eq!(scalar::<i64>(&conn, "SELECT COUNT(*) FROM ledger"), 2);
+    }
+
+    #[test]
+    fn base64_images_move_to_blobs() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate_to(&mut conn, 11).unwrap();
+        conn.execute_batch(
+            "INSERT INTO skins (name, image_base64) VALUES ('Raw', 'AAEC');
+             INSERT INTO skins (name, image_base64) VALUES ('Uri', 'data:image/png;base64,AwQF');
+             INSERT INTO skins (name, image_base64) VALUES ('Broken', 'not base64!');
+             INSERT INTO skins (name) VALUES ('None');",
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+        assert!(!has_column(&conn, "skins", "image_base64").unwrap());
+        let mut stmt = conn
+            .prepare("SELECT s.name, im.image FROM skin_images im JOIN skins s ON s.id = im.skin_id ORDER BY s.id")
+            .unwrap();
+        let images: Vec<(String, Vec<u8>)> =
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(images, [("Raw".to_string(), vec![0, 1, 2]), ("Uri".to_string(), vec![3, 4, 5])]);
+    }
+
+    #[test]
+    fn base_price_starts_at_the_catalog_price() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate_to(&mut conn, 13).unwrap();
+        conn.execute_batch("INSERT INTO skins (name, price) VALUES ('Priced', 1234);").unwrap();
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(scalar::<i64>(&conn, "SELECT base_price FROM skins WHERE name = 'Priced'"), 1234);
+        assert_eq!(scalar::<i64>(&conn, "SELECT COUNT(*) FROM price_history"), 0);
+    }
+
+    #[test]
+    fn newer_database_is_rejected() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", LATEST_VERSION + 1).unwrap();
+        let err = migrate(&mut conn).unwrap_err().to_string();
+        assert!(err.contains("newer than this app supports"), "{}", err);
+        assert_eq!(scalar::<i64>(&conn, "SELECT COUNT(*) FROM sqlite_master"), 0);
+    }
+
+    #[test]
+    fn failed_migration_is_rolled_back() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // A legacy inventory row whose owner does not exist cannot be converted
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, username TEXT NOT NULL UNIQUE, password TEXT NOT NULL);
+             CREATE TABLE inventory (id INTEGER PRIMARY KEY, user_id INTEGER NOT NULL, skin_name TEXT NOT NULL,
+                                     rarity TEXT, price REAL DEFAULT 0.0, quantity INTEGER NOT NULL DEFAULT 1);
+             INSERT INTO inventory (user_id, skin_name) VALUES (42, 'Orphan | Skin');",
+        )
+        .unwrap();
+
+        let err = migrate(&mut conn).unwrap_err().to_string();
+        assert!(err.contains("migration 1 (initial schema)"), "{}", err);
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert!(has_column(&conn, "inventory", "skin_name").unwrap());
+        assert_eq!(scalar::<i64>(&conn, "SELECT COUNT(*) FROM sqlite_master WHERE name = 'skins'"), 0);
+    }
+}
@@ -1,31 +1,51 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod accounts;
 mod db;
 mod models;
+mod password;
+mod session;
+mod state;
 mod ui;
 mod scripts;
 
 use eframe::egui;
+use std::collections::HashSet;
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
 use std::{env, path::PathBuf};
 
-/// Small helper storing a texture handle and original image size
-pub struct TextureInfo {
-    pub handle: egui::TextureHandle,
-    pub size: [usize; 2],
-}
-
 /// State used while animating an open-case roll.
 pub struct OpenCaseState {
     pub options: Vec<crate::models::Skin>,
     pub selected: crate::models::Skin,
-    pub selected_inv_id: i64,
+    pub selected_item: crate::models::InventoryItem,
     pub start: Instant,
     pub duration: Duration,
     pub finished: bool,
 }
 
+/// Input fields of the Profile screen.
+#[derive(Default)]
+pub struct ProfileForm {
+    pub new_username: String,
+    pub old_password: String,
+    pub new_password: String,
+    pub confirm_password: String,
+    pub delete_password: String,
+    // Set once "Delete account" was clicked; the password prompt is shown then
+    pub confirm_delete: bool,
+}
+
+/// Search toolbar of the Buy screen. Kept on `CsApp` so the filters survive
+/// switching screens.
+#[derive(Default)]
+pub struct BuyFilterForm {
+    pub filter: models::SkinFilter,
+    // Price bounds as typed, in dollars; parsed into `filter` every frame
+    pub min_price: String,
+    pub max_price: String,
+}
+
 /// Represents the different screens in the application
 #[derive(Clone)]
 pub enum Screen {
@@ -37,6 +57,8 @@ pub enum Screen {
     Tradeup,
     OpenSkins,
     Inventory,
+    History,
+    Profile,
 }
 
 // Since we are useing the same structure for both login and registration, we define an enum to differentiate the modes
@@ -48,7 +70,10 @@ pub enum AuthMode {
 
 pub struct CsApp {
     screen: Screen,
-    pub db_path: String,
+    /// The one open database connection, shared by every screen.
+    pub db: db::Database,
+    /// Catalog, inventory and balance cached between frames.
+    pub state: state::AppState,
 
     // current logged-in user id (set after successful auth)
     pub current_user_id: Option<i64>,
@@ -58,14 +83,42 @@ pub struct CsApp {
     pub password: String,
     pub message: String,
     splash_deadline: Instant,
-    // Texture cache for loaded skin images. Keyed by skin name (or id as string).
-    pub textures: HashMap<String, TextureInfo>,
+    // Decoded skin and case images, bounded by texture memory
+    pub images: ui::images::ImageCache,
     // Optional open-case animation state
     pub open_case_state: Option<OpenCaseState>,
+    // Case picked on the Open Skins screen
+    pub selected_case: Option<i64>,
     // Inventory ids selected for a tradeup
     pub tradeup_selection: Vec<i64>,
+    // Inventory ids ticked on the Inventory screen for bulk selling
+    pub inventory_selection: HashSet<i64>,
+    // Inventory stacks opened to list their individual copies
+    pub inventory_expanded: HashSet<scripts::inventory::StackKey>,
+    // Contract type chosen on the Tradeup screen
+    pub tradeup_kind: scripts::tradeup::ContractKind,
+    // Result of the last completed tradeup, shown with its outcome distribution
+    pub last_tradeup: Option<scripts::tradeup::TradeupResult>,
     // Selected skin id in the Buy screen
     pub buy_selection: Option<i64>,
+    // Variant (normal / StatTrak™ / Souvenir) bought from the Buy screen
+    pub buy_variant: models::Variant,
+    // Search, filters and sort of the Buy catalog
+    pub buy_filter: BuyFilterForm,
+    // Ledger kind shown on the History screen (None = all)
+    pub history_filter: Option<models::LedgerKind>,
+    // Form state of the Profile screen
+    pub profile: ProfileForm,
+    // Username / password rules and login lockout settings
+    pub account_rules: accounts::AccountRules,
+    // Elasticity, mean reversion and buy/sell spread of the simulated market
+    pub market: scripts::market::MarketConfig,
+    // StatTrak™ chance of unboxed items
+    pub case_config: scripts::open_skins::CaseConfig,
+    // "Remember me" checkbox on the login screen
+    pub remember_me: bool,
+    // Token of the remembered session, revoked on logout
+    pub session_token: Option<String>,
 }
 
 impl Default for CsApp {
@@ -75,27 +128,74 @@ impl Default for CsApp {
        
         // Try to initialize the database, capture any error message for debugging
         let mut message = String::new();
-        if let Err(e) = db::init_db(&db_path) {
-            message = format!("DB init error: {}", e);
-        }
+        let db = match db::Database::open(&db_path) {
+            Ok(db) => db,
+            Err(e) => {
+                // Keep the app usable for this run without touching the broken file
+                message = format!("DB init error: {}", e);
+                db::Database::open_in_memory().expect("in-memory database")
+            }
+        };
+
+        // Log a remembered user straight back in
+        let restored = if message.is_empty() { session::restore(&db) } else { None };
+        let (screen, current_user_id, username, session_token) = match restored {
+            Some((user, token)) => (Screen::LoggedIn(user.username.clone()), Some(user.id), user.username, Some(token)),
+            None => (Screen::MainMenu, None, String::new(), None),
+        };
 
         Self {
-            screen: Screen::MainMenu,
-            db_path,
-            current_user_id: None,
-            username: String::new(),
+            screen,
+            db,
+            state: state::AppState::default(),
+            current_user_id,
+            username,
             password: String::new(),
             message,
-            textures: HashMap::new(),
+            images: ui::images::ImageCache::default(),
             open_case_state: None,
+            selected_case: None,
             tradeup_selection: Vec::new(),
+            inventory_selection: HashSet::new(),
+            inventory_expanded: HashSet::new(),
+            tradeup_kind: scripts::tradeup::ContractKind::Standard,
+            last_tradeup: None,
             buy_selection: None,
+            buy_variant: models::Variant::Normal,
+            buy_filter: BuyFilterForm::default(),
+            history_filter: None,
+            profile: ProfileForm::default(),
+            account_rules: accounts::AccountRules::default(),
+            market: scripts::market::MarketConfig::default(),
+            case_config: scripts::open_skins::CaseConfig::default(),
+            remember_me: false,
+            session_token,
             // Show a little splash screen for 10 seconds (ui/splash.rs)
             splash_deadline: Instant::now() + Duration::from_secs(5),
         }
     }
 }
 
+impl CsApp {
+    /// Back to the main menu with nothing left of the signed-out user: their id,
+    /// the login fields, cached data and every per-user selection. The caller
+    /// deals with the session token.
+    pub fn sign_out(&mut self) {
+        self.screen = Screen::MainMenu;
+        self.current_user_id = None;
+        self.username.clear();
+        self.password.clear();
+        self.state.clear();
+        self.open_case_state = None;
+        self.tradeup_selection.clear();
+        self.inventory_selection.clear();
+        self.inventory_expanded.clear();
+        self.last_tradeup = None;
+        self.buy_selection = None;
+        self.profile = ProfileForm::default();
+    }
+}
+
 /// Compute a sensible default DB file path for the current platform.
 ///
 /// On Windows this prefers `%APPDATA%\CsTradeUp\cs_trade_up.db`.
@@ -188,6 +288,8 @@ impl eframe::App for CsApp {
             Screen::Tradeup => ui::screens::tradeup::show_tradeup(self, ctx),
             Screen::OpenSkins => ui::screens::open_skins::show_open_skins(self, ctx),
             Screen::Inventory => ui::screens::inventory::show_inventory(self, ctx),
+            Screen::History => ui::screens::history::show_history(self, ctx),
+            Screen::Profile => ui::screens::profile::show_profile(self, ctx),
         }
     }
 }
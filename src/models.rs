@@ -1,10 +1,12 @@
-#[derive(Debug, Clone)] /// Represents the data structure of a user in the system
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+
+#[derive(Debug, Clone)] /// Represents the data structure of a user in the system.
+/// The password hash stays in the database and is never loaded into a `User`.
 pub struct User {
     pub id: i64,
     pub username: String,
-    pub password: String,
-    /// User account balance (stored as REAL in SQLite)
-    pub balance: f64,
+    /// User account balance (stored as integer cents in SQLite)
+    pub balance: Money,
 }
 
 #[derive(Debug, Clone)] // Represents a single inventory item owned by a user.
@@ -12,6 +14,12 @@ pub struct InventoryItem {
     pub id: i64,
     pub user_id: i64,
     pub skin_id: i64,
+    /// Float value (0.0–1.0) rolled when the item was created.
+    pub float_value: f64,
+    /// Wear tier derived from `float_value`.
+    pub wear: Wear,
+    /// StatTrak™ / Souvenir flag of this copy.
+    pub variant: Variant,
 }
 
 
@@ -20,12 +28,48 @@ pub struct InventoryItem {
 pub struct Skin {
     pub id: i64,
     pub name: String,
-    pub rarity: Option<String>,
-    pub price: f64,
+    pub rarity: Option<Rarity>,
+    /// Live market price, moved by buys and sells (see `scripts::market`).
+    pub price: Money,
+    /// Catalog price the live price reverts toward.
+    pub base_price: Money,
     pub collection: Option<String>,
     pub weapon_type: Option<String>,
-    /// Image stored as base64 data URI or raw base64 string
+    /// Whether an image is stored for this skin; load it with `Database::skin_image`.
+    pub has_image: bool,
+    /// Lowest float value this skin can drop with.
+    pub min_float: f64,
+    /// Highest float value this skin can drop with.
+    pub max_float: f64,
+}
+
+#[derive(Debug, Clone)]
+/// A case that can be opened, together with the collections it drops from.
+pub struct Case {
+    pub id: i64,
+    pub name: String,
+    pub price: Money,
+    /// Price of the key needed to open the case.
+    pub key_price: Money,
     pub image_base64: Option<String>,
+    /// Collections whose regular (non rare special) skins can drop.
+    pub collections: Vec<String>,
+    /// Collections whose rare special skins (knives, gloves) can drop.
+    pub rare_special_collections: Vec<String>,
+    /// Drop chance (in percent) per rarity tier; tiers not listed never drop.
+    pub odds: Vec<(Rarity, f64)>,
+}
+
+impl Case {
+    /// Total cost of opening the case once: the case plus its key.
+    pub fn open_cost(&self) -> Money {
+        self.price + self.key_price
+    }
+
+    /// Drop chance (in percent) of the given rarity; 0 if the case lists none.
+    pub fn rarity_odds(&self, rarity: Rarity) -> f64 {
+        self.odds.iter().find(|(r, _)| *r == rarity).map(|(_, o)| *o).unwrap_or(0.0)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -35,3 +79,559 @@ pub struct OwnedSkin {
     pub inventory: InventoryItem,
     pub skin: Option<Skin>,
 }
+
+#[derive(Debug, Clone)]
+/// One row of the `price_history` table: a skin's live price after a trade moved it.
+pub struct PricePoint {
+    pub price: Money,
+    /// The trade that moved it: `Buy` or `Sell`.
+    pub kind: LedgerKind,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+/// One row of the `ledger` table: a single change to a user's balance and why it happened.
+pub struct LedgerEntry {
+    pub id: i64,
+    /// Signed change: negative for money spent, positive for money received.
+    pub amount: Money,
+    pub kind: LedgerKind,
+    /// Catalog skin bought, sold, unboxed or produced, if any.
+    pub skin_id: Option<i64>,
+    /// Inventory row created or consumed, if any. Sold items no longer exist.
+    pub inventory_id: Option<i64>,
+    /// UTC time of the change, as SQLite's `datetime('now')` ("YYYY-MM-DD HH:MM:SS").
+    pub created_at: String,
+    /// Name of `skin_id`, joined in for display.
+    pub skin_name: Option<String>,
+}
+
+/// Reason a ledger entry was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LedgerKind {
+    Buy,
+    Sell,
+    Case,
+    Tradeup,
+    /// Money added to an account, e.g. the starting balance.
+    Deposit,
+    /// Manual corrections and opening balances of accounts that predate the ledger.
+    Admin,
+}
+
+impl LedgerKind {
+    pub const ALL: [LedgerKind; 6] = [
+        LedgerKind::Buy,
+        LedgerKind::Sell,
+        LedgerKind::Case,
+        LedgerKind::Tradeup,
+        LedgerKind::Deposit,
+        LedgerKind::Admin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerKind::Buy => "buy",
+            LedgerKind::Sell => "sell",
+            LedgerKind::Case => "case",
+            LedgerKind::Tradeup => "tradeup",
+            LedgerKind::Deposit => "deposit",
+            LedgerKind::Admin => "admin",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LedgerKind::Buy => "Buy",
+            LedgerKind::Sell => "Sell",
+            LedgerKind::Case => "Case",
+            LedgerKind::Tradeup => "Trade-up",
+            LedgerKind::Deposit => "Deposit",
+            LedgerKind::Admin => "Admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        LedgerKind::ALL.into_iter().find(|k| k.as_str().eq_ignore_ascii_case(s.trim()))
+    }
+}
+
+impl ToSql for LedgerKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for LedgerKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let s = value.as_str()?;
+        LedgerKind::parse(s).ok_or_else(|| FromSqlError::Other(format!("unknown ledger kind '{}'", s).into()))
+    }
+}
+
+/// An amount of money in integer cents, so balances and prices add up exactly.
+/// Stored as an INTEGER column; displayed as dollars, e.g. "$12.34".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Money(cents)
+    }
+
+    /// Convert a dollar amount (e.g. from seed JSON), rounding to the nearest cent.
+    pub fn from_dollars(dollars: f64) -> Self {
+        Money((dollars * 100.0).round() as i64)
+    }
+
+    pub fn cents(&self) -> i64 {
+        self.0
+    }
+
+    /// Dollar value as a float, for ratios and charts only; never for arithmetic on balances.
+    pub fn as_dollars(&self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    /// Multiply by `factor` (wear / variant multipliers, probabilities), rounding to the nearest cent.
+    pub fn scale(&self, factor: f64) -> Self {
+        Money((self.0 as f64 * factor).round() as i64)
+    }
+}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{}${}.{:02}", sign, abs / 100, abs % 100)
+    }
+}
+
+impl std::ops::Add for Money {
+    type Output = Money;
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl std::ops::Sub for Money {
+    type Output = Money;
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl std::ops::AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        self.0 += rhs.0;
+    }
+}
+
+impl std::ops::SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl std::ops::Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl std::iter::Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        Money(iter.map(|m| m.0).sum())
+    }
+}
+
+impl ToSql for Money {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0))
+    }
+}
+
+impl FromSql for Money {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value.as_i64().map(Money)
+    }
+}
+
+/// Rarity tier of a catalog skin, ordered from lowest to highest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rarity {
+    Consumer,
+    Industrial,
+    MilSpec,
+    Restricted,
+    Classified,
+    Covert,
+    /// Knives and gloves.
+    RareSpecial,
+}
+
+impl Rarity {
+    pub const ALL: [Rarity; 7] = [
+        Rarity::Consumer,
+        Rarity::Industrial,
+        Rarity::MilSpec,
+        Rarity::Restricted,
+        Rarity::Classified,
+        Rarity::Covert,
+        Rarity::RareSpecial,
+    ];
+
+    /// Default chance (in percent) of a case drop landing in each tier, as in CS.
+    /// Cases can override these with their own `odds`.
+    pub const DEFAULT_CASE_ODDS: [(Rarity, f64); 5] = [
+        (Rarity::MilSpec, 79.92),
+        (Rarity::Restricted, 15.98),
+        (Rarity::Classified, 3.2),
+        (Rarity::Covert, 0.64),
+        (Rarity::RareSpecial, 0.26),
+    ];
+
+    /// Canonical lowercase name, as stored in the `skins.rarity` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rarity::Consumer => "consumer",
+            Rarity::Industrial => "industrial",
+            Rarity::MilSpec => "mil-spec",
+            Rarity::Restricted => "restricted",
+            Rarity::Classified => "classified",
+            Rarity::Covert => "covert",
+            Rarity::RareSpecial => "rare special",
+        }
+    }
+
+    /// Display name, e.g. "Mil-Spec" or "Rare Special".
+    pub fn label(&self) -> &'static str {
+        match self {
+            Rarity::Consumer => "Consumer Grade",
+            Rarity::Industrial => "Industrial Grade",
+            Rarity::MilSpec => "Mil-Spec",
+            Rarity::Restricted => "Restricted",
+            Rarity::Classified => "Classified",
+            Rarity::Covert => "Covert",
+            Rarity::RareSpecial => "Rare Special",
+        }
+    }
+
+    /// Older spellings found in seed data and legacy databases, accepted by `parse`
+    /// besides the canonical names and display labels.
+    const ALIASES: [(&'static str, Rarity); 5] = [
+        ("common", Rarity::Consumer),
+        ("milspec", Rarity::MilSpec),
+        ("mil spec", Rarity::MilSpec),
+        ("mil-spec grade", Rarity::MilSpec),
+        ("rare", Rarity::RareSpecial),
+    ];
+
+    /// Parse a rarity name: a canonical name, a display label or one of the
+    /// `ALIASES`, ignoring case and surrounding whitespace. `None` for anything
+    /// else, including names that merely contain a tier ("not covert").
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        Rarity::ALL
+            .into_iter()
+            .find(|r| r.as_str() == s || r.label().to_lowercase() == s)
+            .or_else(|| Rarity::ALIASES.iter().find(|(alias, _)| *alias == s).map(|(_, r)| *r))
+    }
+
+    /// The tier above this one; `None` for Rare Special.
+    pub fn next(&self) -> Option<Self> {
+        Rarity::ALL.get(*self as usize + 1).copied()
+    }
+
+    /// RGB color CS uses for this tier.
+    pub fn color(&self) -> [u8; 3] {
+        match self {
+            Rarity::Consumer => [200, 200, 200],
+            Rarity::Industrial => [102, 178, 255],
+            Rarity::MilSpec => [40, 120, 255],
+            Rarity::Restricted => [178, 102, 255],
+            Rarity::Classified => [255, 102, 178],
+            Rarity::Covert => [255, 60, 60],
+            Rarity::RareSpecial => [255, 200, 0],
+        }
+    }
+}
+
+impl ToSql for Rarity {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Rarity {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let s = value.as_str()?;
+        Rarity::parse(s).ok_or_else(|| FromSqlError::Other(format!("unknown rarity '{}'", s).into()))
+    }
+}
+
+/// Exterior wear tier of an owned skin, bucketed from its float value
+/// using the same boundaries as CS.
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Wear {
+    FactoryNew,
+    MinimalWear,
+    FieldTested,
+    WellWorn,
+    BattleScarred,
+}
+
+impl Wear {
+    pub const ALL: [Wear; 5] = [
+        Wear::FactoryNew,
+        Wear::MinimalWear,
+        Wear::FieldTested,
+        Wear::WellWorn,
+        Wear::BattleScarred,
+    ];
+
+    /// Bucket a float value into its wear tier.
+    pub fn from_float(float_value: f64) -> Self {
+        if float_value < 0.07 {
+            Wear::FactoryNew
+        } else if float_value < 0.15 {
+            Wear::MinimalWear
+        } else if float_value < 0.38 {
+            Wear::FieldTested
+        } else if float_value < 0.45 {
+            Wear::WellWorn
+        } else {
+            Wear::BattleScarred
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Wear::FactoryNew => "Factory New",
+            Wear::MinimalWear => "Minimal Wear",
+            Wear::FieldTested => "Field-Tested",
+            Wear::WellWorn => "Well-Worn",
+            Wear::BattleScarred => "Battle-Scarred",
+        }
+    }
+
+    /// Short label used on small tiles, e.g. "FN" or "BS".
+    pub fn short(&self) -> &'static str {
+        match self {
+            Wear::FactoryNew => "FN",
+            Wear::MinimalWear => "MW",
+            Wear::FieldTested => "FT",
+            Wear::WellWorn => "WW",
+            Wear::BattleScarred => "BS",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Wear::ALL
+            .into_iter()
+            .find(|w| w.as_str().eq_ignore_ascii_case(s.trim()) || w.short().eq_ignore_ascii_case(s.trim()))
+    }
+
+    /// Multiplier applied to a skin's catalog price (which is quoted for
+    /// Field-Tested) when valuing a copy of this wear.
+    pub fn price_multiplier(&self) -> f64 {
+        match self {
+            Wear::FactoryNew => 1.5,
+            Wear::MinimalWear => 1.2,
+            Wear::FieldTested => 1.0,
+            Wear::WellWorn => 0.85,
+            Wear::BattleScarred => 0.7,
+        }
+    }
+}
+
+impl ToSql for Wear {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Wear {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let s = value.as_str()?;
+        Wear::parse(s).ok_or_else(|| FromSqlError::Other(format!("unknown wear '{}'", s).into()))
+    }
+}
+
+/// Special variant of an owned copy. A copy is at most one of these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Variant {
+    #[default]
+    Normal,
+    StatTrak,
+    Souvenir,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 3] = [Variant::Normal, Variant::StatTrak, Variant::Souvenir];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Variant::Normal => "Normal",
+            Variant::StatTrak => "StatTrak™",
+            Variant::Souvenir => "Souvenir",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
+            "normal" | "" => Some(Variant::Normal),
+            "stattrak™" | "stattrak" | "st" => Some(Variant::StatTrak),
+            "souvenir" => Some(Variant::Souvenir),
+            _ => None,
+        }
+    }
+
+    /// Market name of a copy of `skin_name` in this variant, e.g. "StatTrak™ AK-47 | Redline".
+    pub fn display_name(&self, skin_name: &str) -> String {
+        match self {
+            Variant::Normal => skin_name.to_string(),
+            _ => format!("{} {}", self.as_str(), skin_name),
+        }
+    }
+
+    /// Multiplier applied on top of the catalog price for this variant.
+    pub fn price_multiplier(&self) -> f64 {
+        match self {
+            Variant::Normal => 1.0,
+            Variant::StatTrak => 2.0,
+            Variant::Souvenir => 1.6,
+        }
+    }
+}
+
+impl ToSql for Variant {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Variant {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let s = value.as_str()?;
+        Variant::parse(s).ok_or_else(|| FromSqlError::Other(format!("unknown variant '{}'", s).into()))
+    }
+}
+
+/// Count and value of the owned copies in one group of an `InventoryValue` breakdown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueGroup<K> {
+    pub key: K,
+    pub count: usize,
+    pub value: Money,
+}
+
+/// What a user's inventory is worth at current catalog prices, summed by
+/// `Database::inventory_value`. Each copy is valued at its sale price.
+#[derive(Debug, Clone, Default)]
+pub struct InventoryValue {
+    pub count: usize,
+    pub total: Money,
+    /// Highest rarity first; skins without a rarity last.
+    pub by_rarity: Vec<ValueGroup<Option<Rarity>>>,
+    /// Most valuable collection first.
+    pub by_collection: Vec<ValueGroup<Option<String>>>,
+    /// The most valuable copies and their value, most valuable first.
+    pub top_items: Vec<(OwnedSkin, Money)>,
+}
+
+/// Which catalog skins `Database::search_skins` returns, and in what order. Empty
+/// and `None` fields match every skin.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkinFilter {
+    /// Case-insensitive part of the skin name.
+    pub name: String,
+    pub rarity: Option<Rarity>,
+    pub collection: Option<String>,
+    pub weapon_type: Option<String>,
+    /// Inclusive bounds on the catalog price.
+    pub min_price: Option<Money>,
+    pub max_price: Option<Money>,
+    pub sort: SkinSort,
+    pub descending: bool,
+}
+
+/// Sort order of catalog searches; ties are broken by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkinSort {
+    #[default]
+    Name,
+    Price,
+    Rarity,
+}
+
+impl SkinSort {
+    pub const ALL: [SkinSort; 3] = [SkinSort::Name, SkinSort::Price, SkinSort::Rarity];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkinSort::Name => "Name",
+            SkinSort::Price => "Price",
+            SkinSort::Rarity => "Rarity",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rarity_parses_names_labels_and_aliases_only() {
+        assert_eq!(Rarity::parse("covert"), Some(Rarity::Covert));
+        assert_eq!(Rarity::parse("  Mil-Spec "), Some(Rarity::MilSpec));
+        assert_eq!(Rarity::parse("Consumer Grade"), Some(Rarity::Consumer));
+        assert_eq!(Rarity::parse("RARE SPECIAL"), Some(Rarity::RareSpecial));
+        assert_eq!(Rarity::parse("Mil-Spec Grade"), Some(Rarity::MilSpec));
+        assert_eq!(Rarity::parse("milspec"), Some(Rarity::MilSpec));
+        assert_eq!(Rarity::parse("common"), Some(Rarity::Consumer));
+        assert_eq!(Rarity::parse("rare"), Some(Rarity::RareSpecial));
+        for junk in ["not covert", "uncommon", "covert!", "rarely", "mil", ""] {
+            assert_eq!(Rarity::parse(junk), None, "{:?} should not parse", junk);
+        }
+        for r in Rarity::ALL {
+            assert_eq!(Rarity::parse(r.as_str()), Some(r));
+        }
+    }
+
+    #[test]
+    fn rarity_next_climbs_one_tier() {
+        assert_eq!(Rarity::Consumer.next(), Some(Rarity::Industrial));
+        assert_eq!(Rarity::Classified.next(), Some(Rarity::Covert));
+        assert_eq!(Rarity::Covert.next(), Some(Rarity::RareSpecial));
+        assert_eq!(Rarity::RareSpecial.next(), None);
+    }
+
+    #[test]
+    fn wear_tiers_start_at_the_cs_boundaries() {
+        let tiers: Vec<Wear> = [0.0, 0.0699, 0.07, 0.1499, 0.15, 0.3799, 0.38, 0.4499, 0.45, 1.0]
+            .into_iter()
+            .map(Wear::from_float)
+            .collect();
+        assert_eq!(
+            tiers,
+            [
+                Wear::FactoryNew,
+                Wear::FactoryNew,
+                Wear::MinimalWear,
+                Wear::MinimalWear,
+                Wear::FieldTested,
+                Wear::FieldTested,
+                Wear::WellWorn,
+                Wear::WellWorn,
+                Wear::BattleScarred,
+                Wear::BattleScarred,
+            ]
+        );
+    }
+}
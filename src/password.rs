@@ -0,0 +1,81 @@
+//! Password hashing. Passwords are stored as Argon2id PHC strings
+//! ("$argon2id$v=19$m=...,t=...,p=...$salt$hash") with a random salt per user.
+//! Databases from before hashing hold plain text; those rows are recognised by
+//! `is_hashed` and rehashed on the user's next successful login.
+
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+
+/// Hash `password` with Argon2id and a fresh random salt.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| format!("failed to hash password: {}", e))
+}
+
+/// Argon2id hash, with the default parameters, of a password no account uses.
+const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$aO+74K++Pw5u0g/Iahzd7g$b6n9Qq1DiNQ97MX9tMA9YWGeDjUiUtasaAG7VgRhZCQ";
+
+/// Run a full Argon2 verification of `password` against a dummy hash, so a login
+/// for an unknown username takes as long as one for a real account.
+pub fn verify_dummy(password: &str) {
+    let _ = verify_password(password, DUMMY_HASH);
+}
+
+/// Whether a stored value is a PHC hash rather than a legacy plaintext password.
+pub fn is_hashed(stored: &str) -> bool {
+    stored.starts_with("$argon2")
+}
+
+/// Check `password` against a stored value. Hashes are verified by Argon2, which
+/// compares in constant time; legacy plaintext values are compared in constant time
+/// too, so the caller can rehash them after a match.
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    if is_hashed(stored) {
+        match PasswordHash::new(stored) {
+            Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+            Err(_) => false,
+        }
+    } else {
+        constant_time_eq(password.as_bytes(), stored.as_bytes())
+    }
+}
+
+/// Compare two byte strings without returning early on the first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_are_salted_and_verify() {
+        let a = hash_password("hunter2").unwrap();
+        let b = hash_password("hunter2").unwrap();
+        assert!(a.starts_with("$argon2id$"));
+        assert_ne!(a, b);
+        assert!(verify_password("hunter2", &a));
+        assert!(!verify_password("hunter3", &a));
+    }
+
+    #[test]
+    fn legacy_plaintext_still_verifies() {
+        assert!(!is_hashed("hunter2"));
+        assert!(verify_password("hunter2", "hunter2"));
+        assert!(!verify_password("hunter", "hunter2"));
+    }
+
+    #[test]
+    fn dummy_hash_costs_as_much_as_a_real_one() {
+        let params = |hash: &str| hash.split('$').take(4).collect::<Vec<_>>().join("$");
+        assert_eq!(params(DUMMY_HASH), params(&hash_password("hunter2").unwrap()));
+        assert!(PasswordHash::new(DUMMY_HASH).is_ok());
+    }
+}
@@ -1,24 +1,103 @@
-use crate::db;
+use crate::db::Database;
+use crate::state::AppState;
+use crate::models::{LedgerKind, Money, Skin, Variant};
+use crate::scripts::market::MarketConfig;
+
+/// Price of buying a copy of `skin` in the given variant: its live price scaled by
+/// the variant, plus the buyer's side of the market spread.
+pub fn buy_price(skin: &Skin, variant: Variant, market: &MarketConfig) -> Money {
+    market.ask(skin.price.scale(variant.price_multiplier()))
+}
 
 /// Attempt to purchase a skin for a user.
-/// Performs balance checks, updates the user's balance and inserts an inventory row.
+/// Charges `buy_price` and inserts an inventory row of the requested `variant` with a
+/// float rolled inside the skin's float range, both in one transaction: if either
+/// step fails (unknown skin, not enough funds) nothing is changed.
+/// The purchase pushes the skin's live price up (`market::record_trade`).
 /// Returns Ok(()) on success or Err(String) with a user-friendly error message.
-pub fn attempt_buy(db_path: &str, user_id: i64, skin_id: i64, price: f64) -> Result<(), String> {
-    match db::get_user_by_id(db_path, user_id) {
-        Ok(Some(user)) => {
-            if user.balance < price {
-                return Err("Not enough funds to buy this skin".into());
-            }
-
-            // Deduct balance
-            db::change_user_balance(db_path, user_id, -price)?;
-
-            // Add inventory row for the purchased skin
-            db::add_inventory_item(db_path, user_id, skin_id)?;
-
-            Ok(())
-        }
-        Ok(None) => Err("User not found".into()),
-        Err(e) => Err(e),
+/// A successful purchase invalidates the user's cached inventory and balance and
+/// the cached catalog prices.
+pub fn attempt_buy(
+    db: &Database,
+    state: &mut AppState,
+    market: &MarketConfig,
+    user_id: i64,
+    skin_id: i64,
+    variant: Variant,
+) -> Result<(), String> {
+    let tx = db.transaction()?;
+
+    let skin = db.find_skin(skin_id)?.ok_or_else(|| "Skin not found".to_string())?;
+    let price = buy_price(&skin, variant, market);
+    if db.user_balance(user_id)? < price {
+        return Err("Not enough funds to buy this skin".into());
+    }
+
+    // Roll a float for the purchased copy and add its inventory row
+    let float_value = crate::scripts::inventory::roll_float(&skin, &mut rand::thread_rng());
+    let item = db.add_inventory_item(user_id, skin_id, float_value, variant)?;
+
+    // Charge for it; the debit re-checks the balance, so a failure rolls back the new row
+    db.debit_balance(user_id, price, LedgerKind::Buy, Some(skin_id), Some(item.id))?;
+    crate::scripts::market::record_trade(db, market, &skin, LedgerKind::Buy)?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+    state.invalidate_user();
+    state.invalidate_catalog();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{balance, temp_db, user_and_skin};
+
+    #[test]
+    fn buy_charges_and_adds_item() {
+        let db = temp_db("buy_ok");
+        let (user_id, skin_id) = user_and_skin(&db);
+
+        let mut state = AppState::default();
+        assert!(state.inventory(&db, user_id).unwrap().is_empty());
+        attempt_buy(&db, &mut state, &MarketConfig::FIXED, user_id, skin_id, Variant::StatTrak).unwrap();
+
+        assert_eq!(balance(&db, user_id), Money::from_cents(8000));
+        // The cached inventory and balance were invalidated by the purchase
+        assert_eq!(state.balance(&db, user_id).unwrap(), Money::from_cents(8000));
+        let items = state.inventory(&db, user_id).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].inventory.variant, Variant::StatTrak);
+    }
+
+    #[test]
+    fn buy_without_funds_changes_nothing() {
+        let db = temp_db("buy_no_funds");
+        let (user_id, skin_id) = user_and_skin(&db);
+        db.debit_balance(user_id, Money::from_cents(9500), LedgerKind::Admin, None, None)
+            .unwrap();
+
+        let err = attempt_buy(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, skin_id, Variant::Normal).unwrap_err();
+
+        assert_eq!(err, "Not enough funds to buy this skin");
+        assert_eq!(balance(&db, user_id), Money::from_cents(500));
+        assert!(db.get_inventory_for_user(user_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn buy_unknown_skin_charges_nothing() {
+        let db = temp_db("buy_unknown_skin");
+        let (user_id, _) = user_and_skin(&db);
+
+        assert!(attempt_buy(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, 9999, Variant::Normal).is_err());
+        assert_eq!(balance(&db, user_id), Money::from_cents(10000));
+    }
+
+    #[test]
+    fn buy_for_unknown_user_fails() {
+        let db = temp_db("buy_unknown_user");
+        let (_, skin_id) = user_and_skin(&db);
+
+        assert_eq!(attempt_buy(&db, &mut AppState::default(), &MarketConfig::FIXED, 9999, skin_id, Variant::Normal).unwrap_err(), "User not found");
+        assert!(db.get_inventory_for_user(9999).unwrap().is_empty());
     }
 }
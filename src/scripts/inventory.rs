@@ -1,48 +1,69 @@
-use crate::models::OwnedSkin;
-use crate::db;
+use crate::models::{Money, OwnedSkin, Rarity, Skin, Variant, Wear};
+use crate::db::Database;
+use crate::state::AppState;
+use rand::Rng;
 
 /// Domain struct describing a skin to be added/managed in inventory.
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct SkinInfo {
     pub name: String,
-    pub rarity: Option<String>,
-    pub price: f64,
+    pub rarity: Option<Rarity>,
+    pub price: Money,
     pub collection: Option<String>,
     pub weapon_type: Option<String>,
-    pub image_base64: Option<String>,
+    /// Encoded image file (PNG, JPEG, ...).
+    pub image: Option<Vec<u8>>,
+    pub min_float: f64,
+    pub max_float: f64,
+}
+
+/// Roll a float value uniformly inside `skin`'s float range.
+pub fn roll_float<R: Rng + ?Sized>(skin: &Skin, rng: &mut R) -> f64 {
+    if skin.max_float > skin.min_float {
+        rng.gen_range(skin.min_float..=skin.max_float)
+    } else {
+        skin.min_float
+    }
 }
 
 /// Add a skin to a user's inventory by ensuring the skin exists in the catalog
 /// and then inserting/updating an inventory row that references the catalog id.
-pub fn add_skin(db_path: &str, user_id: i64, skin: SkinInfo) -> Result<OwnedSkin, String> {
+#[allow(dead_code)]
+pub fn add_skin(db: &Database, state: &mut AppState, user_id: i64, skin: SkinInfo) -> Result<OwnedSkin, String> {
     // Ensure the skin exists in the catalog
-    let existing_skin = db::get_skin_by_name(db_path, &skin.name)?;
+    let existing_skin = db.get_skin_by_name(&skin.name)?;
     let catalog_skin = if let Some(s) = existing_skin {
         s
     } else {
-        db::add_skin(
-            db_path,
+        db.add_skin(
             &skin.name,
-            skin.rarity.as_deref(),
+            skin.rarity,
             skin.price,
             skin.collection.as_deref(),
             skin.weapon_type.as_deref(),
-            skin.image_base64.as_deref(),
+            skin.image.as_deref(),
+            (skin.min_float, skin.max_float),
         )?
     };
 
     // Always insert a new inventory ownership row (duplicates allowed).
-    let inv = db::add_inventory_item(db_path, user_id, catalog_skin.id)?;
+    let float_value = roll_float(&catalog_skin, &mut rand::thread_rng());
+    let inv = db.add_inventory_item(user_id, catalog_skin.id, float_value, Default::default())?;
+    state.invalidate_catalog();
+    state.invalidate_user();
     Ok(OwnedSkin { inventory: inv, skin: Some(catalog_skin) })
 }
 
 /// Remove `quantity` units of the named skin from the user's inventory. If quantity reaches 0, delete the row.
-pub fn remove_skin_by_name(db_path: &str, user_id: i64, skin_name: &str) -> Result<(), String> {
+#[allow(dead_code)]
+pub fn remove_skin_by_name(db: &Database, state: &mut AppState, user_id: i64, skin_name: &str) -> Result<(), String> {
     // Find catalog skin by name and remove a single ownership row for the user (if any)
-    if let Some(skin) = db::get_skin_by_name(db_path, skin_name)? {
-        let items = db::get_inventory_for_user(db_path, user_id)?;
+    if let Some(skin) = db.get_skin_by_name(skin_name)? {
+        let items = db.get_inventory_for_user(user_id)?;
         if let Some(existing) = items.into_iter().find(|it| it.inventory.skin_id == skin.id) {
-            db::remove_inventory_item(db_path, existing.inventory.id)?;
+            db.remove_inventory_item(existing.inventory.id)?;
+            state.invalidate_user();
             Ok(())
         } else {
             Err(format!("No skin named '{}' found for user {}", skin_name, user_id))
@@ -52,15 +73,107 @@ pub fn remove_skin_by_name(db_path: &str, user_id: i64, skin_name: &str) -> Resu
     }
 }
 
-/// Get all inventory items for a user (thin wrapper around db layer).
-pub fn list_inventory(db_path: &str, user_id: i64) -> Result<Vec<OwnedSkin>, String> {
-    crate::db::get_inventory_for_user(db_path, user_id)
+/// Get all inventory items for a user, bypassing the cache in `AppState`.
+#[allow(dead_code)]
+pub fn list_inventory(db: &Database, user_id: i64) -> Result<Vec<OwnedSkin>, String> {
+    db.get_inventory_for_user(user_id)
 }
 
-/// Set quantity of a specific inventory item by id.
+// Set quantity of a specific inventory item by id.
 // set_quantity removed; inventory no longer tracks a numeric quantity.
 
 /// Remove an item by id.
-pub fn remove_item(db_path: &str, item_id: i64) -> Result<(), String> {
-    crate::db::remove_inventory_item(db_path, item_id)
+#[allow(dead_code)]
+pub fn remove_item(db: &Database, state: &mut AppState, item_id: i64) -> Result<(), String> {
+    db.remove_inventory_item(item_id)?;
+    state.invalidate_user();
+    Ok(())
+}
+
+/// What copies in one stack share: catalog skin, wear tier and variant.
+pub type StackKey = (i64, Wear, Variant);
+
+/// Owned copies of the same skin in the same wear tier and variant, shown as one
+/// tile on the Inventory screen. Copies in a stack sell for the same price.
+#[derive(Debug, Clone)]
+pub struct Stack {
+    pub key: StackKey,
+    /// Never empty; lowest float first.
+    pub items: Vec<OwnedSkin>,
 }
+
+impl Stack {
+    pub fn skin(&self) -> Option<&Skin> {
+        self.items[0].skin.as_ref()
+    }
+
+    pub fn count(&self) -> usize {
+        self.items.len()
+    }
+}
+
+/// Group `items` into stacks, in the order each stack's first copy appears.
+pub fn stack_items(items: &[OwnedSkin]) -> Vec<Stack> {
+    let mut stacks: Vec<Stack> = Vec::new();
+    let mut index: std::collections::HashMap<StackKey, usize> = std::collections::HashMap::new();
+    for it in items {
+        let key = (it.inventory.skin_id, it.inventory.wear, it.inventory.variant);
+        match index.get(&key) {
+            Some(&i) => stacks[i].items.push(it.clone()),
+            None => {
+                index.insert(key, stacks.len());
+                stacks.push(Stack { key, items: vec![it.clone()] });
+            }
+        }
+    }
+    for stack in &mut stacks {
+        stack.items.sort_by(|a, b| a.inventory.float_value.total_cmp(&b.inventory.float_value));
+    }
+    stacks
+}
+
+/// Inventory ids of every copy but the lowest-float one in each stack.
+pub fn duplicate_ids(stacks: &[Stack]) -> Vec<i64> {
+    stacks.iter().flat_map(|s| s.items[1..].iter().map(|it| it.inventory.id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{temp_db, user_and_skin};
+
+    #[test]
+    fn copies_stack_by_skin_wear_and_variant() {
+        let db = temp_db("inventory_stacks");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let worse = db.add_inventory_item(user_id, skin_id, 0.30, Variant::Normal).unwrap().id;
+        let best = db.add_inventory_item(user_id, skin_id, 0.20, Variant::Normal).unwrap().id;
+        let stattrak = db.add_inventory_item(user_id, skin_id, 0.25, Variant::StatTrak).unwrap().id;
+        let new = db.add_inventory_item(user_id, skin_id, 0.01, Variant::Normal).unwrap().id;
+        let middle = db.add_inventory_item(user_id, skin_id, 0.25, Variant::Normal).unwrap().id;
+
+        let stacks = stack_items(&db.get_inventory_for_user(user_id).unwrap());
+        let ids: Vec<Vec<i64>> = stacks.iter().map(|s| s.items.iter().map(|it| it.inventory.id).collect()).collect();
+        assert_eq!(ids, [vec![best, middle, worse], vec![stattrak], vec![new]]);
+        assert_eq!(stacks[0].key, (skin_id, Wear::FieldTested, Variant::Normal));
+        assert_eq!(stacks[0].count(), 3);
+
+        assert_eq!(duplicate_ids(&stacks), [middle, worse]);
+    }
+
+    #[test]
+    fn rolled_floats_stay_inside_the_skin_range() {
+        use rand::SeedableRng;
+        let db = temp_db("roll_float");
+        let narrow = db.add_skin("Test | Narrow", Some(Rarity::MilSpec), Money::from_cents(100), None, None, None, (0.06, 0.8)).unwrap();
+        let fixed = db.add_skin("Test | Fixed", Some(Rarity::MilSpec), Money::from_cents(100), None, None, None, (0.3, 0.3)).unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+        for _ in 0..1000 {
+            let f = roll_float(&narrow, &mut rng);
+            assert!((0.06..=0.8).contains(&f), "{} outside the range", f);
+        }
+        assert_eq!(roll_float(&fixed, &mut rng), 0.3);
+    }
+}
+
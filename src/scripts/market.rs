@@ -0,0 +1,140 @@
+//! Simulated market. Every catalog skin has a base price (its catalog price) and a
+//! live price in `skins.price`. Each copy bought pushes the live price up and each
+//! copy sold pushes it down; every trade first pulls the price part of the way
+//! back toward the base price, so it settles there once trading stops going one
+//! way. Buyers pay the live price plus half the spread and sellers get the live
+//! price minus half of it.
+
+use crate::db::Database;
+use crate::models::{LedgerKind, Money, Skin};
+
+/// Tuning of the market, held by `CsApp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketConfig {
+    /// How far one copy bought or sold moves the live price, as a fraction of it.
+    pub elasticity: f64,
+    /// Fraction of the gap between the live and the base price closed on every trade.
+    pub reversion: f64,
+    /// Gap between the buy and the sell price, as a fraction of the live price.
+    pub spread: f64,
+    /// The live price stays between these multiples of the base price.
+    pub min_factor: f64,
+    pub max_factor: f64,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        MarketConfig { elasticity: 0.02, reversion: 0.05, spread: 0.10, min_factor: 0.5, max_factor: 3.0 }
+    }
+}
+
+impl MarketConfig {
+    /// A market whose prices never move, buying and selling at the live price. For
+    /// tests of everything but pricing.
+    #[cfg(test)]
+    pub const FIXED: MarketConfig =
+        MarketConfig { elasticity: 0.0, reversion: 0.0, spread: 0.0, min_factor: 0.0, max_factor: f64::INFINITY };
+
+    /// Factor from market value to what a buyer pays.
+    pub fn ask_multiplier(&self) -> f64 {
+        1.0 + self.spread / 2.0
+    }
+
+    /// Factor from market value to what a seller gets.
+    pub fn bid_multiplier(&self) -> f64 {
+        1.0 - self.spread / 2.0
+    }
+
+    pub fn ask(&self, value: Money) -> Money {
+        value.scale(self.ask_multiplier())
+    }
+
+    pub fn bid(&self, value: Money) -> Money {
+        value.scale(self.bid_multiplier())
+    }
+
+    /// Live price after one copy is traded at `price`: reverted toward `base`, moved
+    /// up for a `Buy` or down for a `Sell`, and kept within the allowed band.
+    pub fn next_price(&self, price: Money, base: Money, kind: LedgerKind) -> Money {
+        let (price, base) = (price.cents() as f64, base.cents() as f64);
+        let reverted = price + (base - price) * self.reversion;
+        let moved = match kind {
+            LedgerKind::Buy => reverted * (1.0 + self.elasticity),
+            _ => reverted * (1.0 - self.elasticity),
+        };
+        // max/min rather than clamp, which panics on a misconfigured band
+        let bounded = moved.max(base * self.min_factor).min(base * self.max_factor);
+        Money::from_cents(bounded.round() as i64)
+    }
+}
+
+/// Move `skin`'s live price for one copy bought or sold (`kind`) and record the
+/// change in `price_history`. Returns the new price. Runs inside the trade's
+/// transaction, so a failed trade leaves the price alone.
+pub fn record_trade(db: &Database, market: &MarketConfig, skin: &Skin, kind: LedgerKind) -> Result<Money, String> {
+    let next = market.next_price(skin.price, skin.base_price, kind);
+    if next != skin.price {
+        db.set_skin_price(skin.id, next, kind)?;
+    }
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{balance, temp_db, user_and_skin};
+    use crate::models::Variant;
+    use crate::state::AppState;
+
+    #[test]
+    fn prices_move_with_trades_and_revert_to_base() {
+        let market = MarketConfig { elasticity: 0.1, reversion: 0.5, spread: 0.0, min_factor: 0.5, max_factor: 2.0 };
+        let base = Money::from_cents(1000);
+
+        assert_eq!(market.next_price(base, base, LedgerKind::Buy), Money::from_cents(1100));
+        assert_eq!(market.next_price(base, base, LedgerKind::Sell), Money::from_cents(900));
+        // Half the way back to $10.00 first, then the nudge
+        assert_eq!(market.next_price(Money::from_cents(1400), base, LedgerKind::Sell), Money::from_cents(1080));
+        // Kept within the band around the base price
+        let sticky = MarketConfig { reversion: 0.0, ..market };
+        assert_eq!(sticky.next_price(Money::from_cents(1900), base, LedgerKind::Buy), Money::from_cents(2000));
+        assert_eq!(sticky.next_price(Money::from_cents(520), base, LedgerKind::Sell), Money::from_cents(500));
+
+        // Repeated buys level off below the ceiling instead of running away
+        let mut price = base;
+        for _ in 0..50 {
+            price = market.next_price(price, base, LedgerKind::Buy);
+        }
+        assert_eq!(price, Money::from_cents(1222));
+    }
+
+    #[test]
+    fn buys_and_sells_trade_at_the_live_price_with_a_spread() {
+        let db = temp_db("market_trades");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let market = MarketConfig { elasticity: 0.1, reversion: 0.0, spread: 0.2, min_factor: 0.5, max_factor: 2.0 };
+        let mut state = AppState::default();
+
+        // Pays $10.00 + 10%, and the next copy costs 10% more
+        crate::scripts::buy::attempt_buy(&db, &mut state, &market, user_id, skin_id, Variant::Normal).unwrap();
+        assert_eq!(balance(&db, user_id), Money::from_cents(10000 - 1100));
+        let skin = db.find_skin(skin_id).unwrap().unwrap();
+        assert_eq!((skin.price, skin.base_price), (Money::from_cents(1100), Money::from_cents(1000)));
+
+        // A Field-Tested copy sells at the live $11.00 less 10%, which pushes the price down again
+        let item = db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap();
+        crate::scripts::sell::sell_item(&db, &mut state, &market, user_id, item.id).unwrap();
+        assert_eq!(balance(&db, user_id), Money::from_cents(10000 - 1100 + 990));
+        assert_eq!(db.find_skin(skin_id).unwrap().unwrap().price, Money::from_cents(990));
+
+        let history: Vec<(Money, LedgerKind)> =
+            db.price_history(skin_id, 10).unwrap().iter().map(|p| (p.price, p.kind)).collect();
+        assert_eq!(history, [(Money::from_cents(990), LedgerKind::Sell), (Money::from_cents(1100), LedgerKind::Buy)]);
+
+        // A failed purchase leaves the price where it was
+        db.debit_balance(user_id, balance(&db, user_id), LedgerKind::Admin, None, None).unwrap();
+        assert!(crate::scripts::buy::attempt_buy(&db, &mut state, &market, user_id, skin_id, Variant::Normal).is_err());
+        assert_eq!(db.find_skin(skin_id).unwrap().unwrap().price, Money::from_cents(990));
+        assert_eq!(db.price_history(skin_id, 10).unwrap().len(), 2);
+    }
+}
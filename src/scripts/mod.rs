@@ -3,3 +3,4 @@ pub mod sell;
 pub mod tradeup;
 pub mod open_skins;
 pub mod inventory;
+pub mod market;
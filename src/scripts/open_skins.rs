@@ -1,54 +1,247 @@
 use rand::prelude::*;
+use crate::db::Database;
+use crate::state::AppState;
+use crate::models::LedgerKind;
 
-/// Open a case for `user_id`, charging `case_cost`. Selects a skin from the
-/// catalog using rarity-weighted randomness, inserts an inventory row and
-/// returns `(inventory_id, Skin)` on success.
-pub fn open_case(db_path: &str, user_id: i64, case_cost: f64) -> Result<(i64, crate::models::Skin), String> {
-    // Ensure user has funds
-    let user = crate::db::get_user_by_id(db_path, user_id).map_err(|e| e)?;
-    let user = user.ok_or_else(|| "User not found".to_string())?;
-    if user.balance < case_cost {
+/// Tuning of case opening, held by `CsApp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaseConfig {
+    /// Chance (0..1) that an unboxed item is StatTrak™.
+    pub stattrak_rate: f64,
+}
+
+impl Default for CaseConfig {
+    fn default() -> Self {
+        CaseConfig { stattrak_rate: 0.1 }
+    }
+}
+
+/// Skins that can drop from `case`: the regular skins of its collections plus
+/// the rare special skins of its rare special collections. Nothing else in
+/// `catalog` can ever drop from it.
+pub fn case_pool(case: &crate::models::Case, catalog: &[crate::models::Skin]) -> Vec<crate::models::Skin> {
+    catalog
+        .iter()
+        .filter(|s| {
+            let Some(collection) = s.collection.as_ref() else { return false };
+            if s.rarity == Some(crate::models::Rarity::RareSpecial) {
+                case.rare_special_collections.contains(collection)
+            } else {
+                case.collections.contains(collection)
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Chance (0..1) of each skin in `pool` dropping from `case`. A rarity tier's odds
+/// are split evenly across the pool's skins of that tier; tiers the pool has no
+/// skins for are left out and the remaining odds renormalized. Skins whose tier
+/// has no odds in the case are omitted.
+pub fn drop_odds(case: &crate::models::Case, pool: &[crate::models::Skin]) -> Vec<(crate::models::Skin, f64)> {
+    let weights: Vec<f64> = pool
+        .iter()
+        .map(|s| match s.rarity {
+            Some(r) => {
+                let tier_size = pool.iter().filter(|x| x.rarity == Some(r)).count() as f64;
+                case.rarity_odds(r) / tier_size
+            }
+            None => 0.0,
+        })
+        .collect();
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+
+    pool.iter()
+        .zip(weights)
+        .filter(|(_, w)| *w > 0.0)
+        .map(|(s, w)| (s.clone(), w / total))
+        .collect()
+}
+
+/// Open case `case_id` for `user_id`, charging the case and key price. Selects a
+/// skin from the case's drop pool using the case's rarity odds, rolls its
+/// float and whether it is StatTrak™ (with `config.stattrak_rate`), inserts
+/// an inventory row and returns `(InventoryItem, Skin)` on success. Charging and
+/// inserting happen in one transaction, so a failure leaves the balance untouched.
+/// The drop pool comes from the cached catalog in `state`.
+pub fn open_case(
+    db: &Database,
+    state: &mut AppState,
+    config: &CaseConfig,
+    user_id: i64,
+    case_id: i64,
+) -> Result<(crate::models::InventoryItem, crate::models::Skin), String> {
+    let case = db.get_case_by_id(case_id)?.ok_or_else(|| "Case not found".to_string())?;
+    let case_cost = case.open_cost();
+
+    // Load the case's drop pool before charging anything
+    let odds = drop_odds(&case, &case_pool(&case, &state.catalog(db)?));
+    if odds.is_empty() {
+        return Err(format!("{} has no skins to drop", case.name));
+    }
+
+    // Choose a random skin according to the case's rarity odds
+    let mut rng = thread_rng();
+    let selected = odds
+        .choose_weighted(&mut rng, |(_, p)| *p)
+        .map_err(|e| e.to_string())?
+        .0
+        .clone();
+
+    // Roll the copy's float and whether it is StatTrak™
+    let float_value = crate::scripts::inventory::roll_float(&selected, &mut rng);
+    let variant = if rng.gen_bool(config.stattrak_rate.clamp(0.0, 1.0)) {
+        crate::models::Variant::StatTrak
+    } else {
+        crate::models::Variant::Normal
+    };
+
+    // Charge the case and insert the inventory row in one transaction
+    let tx = db.transaction()?;
+    if db.user_balance(user_id)? < case_cost {
         return Err("Not enough funds to open case".into());
     }
 
-    // Deduct cost immediately
-    crate::db::change_user_balance(db_path, user_id, -case_cost).map_err(|e| e)?;
+    let inv = db.add_inventory_item(user_id, selected.id, float_value, variant)?;
+
+    // Charge the case; the debit re-checks the balance, so a failure rolls back the new item
+    db.debit_balance(user_id, case_cost, LedgerKind::Case, Some(selected.id), Some(inv.id))?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+    state.invalidate_user();
+    Ok((inv, selected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{balance, temp_db, user_and_skin};
+    use crate::models::Money;
+
+    const NO_STATTRAK: CaseConfig = CaseConfig { stattrak_rate: 0.0 };
+
+    fn test_case(db: &Database, price: Money, collections: &[&str]) -> crate::models::Case {
+        let collections: Vec<String> = collections.iter().map(|c| c.to_string()).collect();
+        db.add_case("Test Case", price, Money::ZERO, None, &collections, &[], &[]).unwrap()
+    }
+
+    #[test]
+    fn open_case_charges_and_adds_item() {
+        let db = temp_db("open_ok");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let case = test_case(&db, Money::from_cents(250), &["Test Collection"]);
+
+        let (item, skin) = open_case(&db, &mut AppState::default(), &NO_STATTRAK, user_id, case.id).unwrap();
+
+        assert_eq!(skin.id, skin_id);
+        assert_eq!(item.user_id, user_id);
+        assert_eq!(balance(&db, user_id), Money::from_cents(9750));
+        assert_eq!(item.variant, crate::models::Variant::Normal);
+
+        let always = CaseConfig { stattrak_rate: 1.0 };
+        let (item, _) = open_case(&db, &mut AppState::default(), &always, user_id, case.id).unwrap();
+        assert_eq!(item.variant, crate::models::Variant::StatTrak);
+    }
+
+    #[test]
+    fn open_case_without_funds_changes_nothing() {
+        let db = temp_db("open_no_funds");
+        let (user_id, _) = user_and_skin(&db);
+        let case = test_case(&db, Money::from_cents(15000), &["Test Collection"]);
+
+        let err = open_case(&db, &mut AppState::default(), &NO_STATTRAK, user_id, case.id).unwrap_err();
 
-    // Load catalog
-    let skins = crate::db::list_skins(db_path).map_err(|e| e)?;
-    if skins.is_empty() {
-        return Err("No skins available in catalog".into());
+        assert_eq!(err, "Not enough funds to open case");
+        assert_eq!(balance(&db, user_id), Money::from_cents(10000));
+        assert!(db.get_inventory_for_user(user_id).unwrap().is_empty());
     }
 
-    // Map rarities to weights
-    fn rarity_weight(r: &Option<String>) -> f64 {
-        match r.as_ref().map(|s| s.to_lowercase().trim().to_string()).as_deref() {
-            Some("common") => 70.0,
-            Some("uncommon") => 20.0,
-            Some("rare") => 8.0,
-            Some("epic") | Some("mythic") | Some("legendary") => 2.0,
-            _ => 10.0,
+    #[test]
+    fn open_unknown_or_empty_case_charges_nothing() {
+        let db = temp_db("open_empty");
+        let (user_id, _) = user_and_skin(&db);
+        let case = test_case(&db, Money::from_cents(100), &["No Such Collection"]);
+
+        assert!(open_case(&db, &mut AppState::default(), &NO_STATTRAK, user_id, 9999).is_err());
+        assert!(open_case(&db, &mut AppState::default(), &NO_STATTRAK, user_id, case.id).is_err());
+        assert_eq!(balance(&db, user_id), Money::from_cents(10000));
+    }
+
+    fn skin(id: i64, rarity: crate::models::Rarity, collection: &str) -> crate::models::Skin {
+        crate::models::Skin {
+            id,
+            name: format!("Test | Skin {}", id),
+            rarity: Some(rarity),
+            price: Money::from_cents(1000),
+            base_price: Money::from_cents(1000),
+            collection: Some(collection.to_string()),
+            weapon_type: None,
+            has_image: false,
+            min_float: 0.0,
+            max_float: 1.0,
         }
     }
 
-    let weights: Vec<f64> = skins.iter().map(|s| rarity_weight(&s.rarity)).collect();
-    let total: f64 = weights.iter().sum();
+    fn case(collections: &[&str], rare_special_collections: &[&str]) -> crate::models::Case {
+        crate::models::Case {
+            id: 1,
+            name: "Test Case".to_string(),
+            price: Money::ZERO,
+            key_price: Money::ZERO,
+            image_base64: None,
+            collections: collections.iter().map(|c| c.to_string()).collect(),
+            rare_special_collections: rare_special_collections.iter().map(|c| c.to_string()).collect(),
+            odds: crate::models::Rarity::DEFAULT_CASE_ODDS.to_vec(),
+        }
+    }
 
-    // Choose a random skin using cumulative weights
-    let mut rng = thread_rng();
-    let mut choice = rng.gen_range(0.0..total);
-    let mut idx = 0usize;
-    while idx < weights.len() {
-        if choice < weights[idx] {
-            break;
+    fn percentages(odds: &[(crate::models::Skin, f64)]) -> Vec<(i64, f64)> {
+        odds.iter().map(|(s, p)| (s.id, (p * 10000.0).round() / 100.0)).collect()
+    }
+
+    #[test]
+    fn default_odds_follow_cs() {
+        use crate::models::Rarity::*;
+        let pool = [skin(1, MilSpec, "A"), skin(2, Restricted, "A"), skin(3, Classified, "A"), skin(4, Covert, "A"), skin(5, RareSpecial, "A")];
+
+        let odds = drop_odds(&case(&["A"], &["A"]), &pool);
+
+        assert_eq!(percentages(&odds), [(1, 79.92), (2, 15.98), (3, 3.2), (4, 0.64), (5, 0.26)]);
+    }
+
+    #[test]
+    fn odds_renormalize_over_the_tiers_present() {
+        use crate::models::Rarity::*;
+        // No Classified or gold: the other tiers share their odds; a tier's skins split it
+        let pool = [skin(1, MilSpec, "A"), skin(2, MilSpec, "A"), skin(3, Restricted, "A"), skin(4, Covert, "A")];
+
+        let odds = drop_odds(&case(&["A"], &[]), &pool);
+
+        let total = 79.92 + 15.98 + 0.64;
+        let expected = [79.92 / 2.0 / total, 79.92 / 2.0 / total, 15.98 / total, 0.64 / total];
+        for ((_, p), e) in odds.iter().zip(expected) {
+            assert!((p - e).abs() < 1e-9, "expected {}, got {}", e, p);
         }
-        choice -= weights[idx];
-        idx += 1;
+        assert!((odds.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-9);
     }
-    if idx >= skins.len() { idx = skins.len() - 1; }
-    let selected = skins[idx].clone();
 
-    // Insert inventory row for the selected skin
-    let inv = crate::db::add_inventory_item(db_path, user_id, selected.id).map_err(|e| e)?;
-    Ok((inv.id, selected))
+    #[test]
+    fn case_pool_only_holds_the_case_collections() {
+        use crate::models::Rarity::*;
+        let catalog = [
+            skin(1, MilSpec, "A"),
+            skin(2, MilSpec, "Elsewhere"),
+            skin(3, RareSpecial, "Knives"),
+            // Gold from a regular collection, or a regular skin from a gold one, never drops
+            skin(4, RareSpecial, "A"),
+            skin(5, Covert, "Knives"),
+        ];
+
+        let pool = case_pool(&case(&["A"], &["Knives"]), &catalog);
+
+        assert_eq!(pool.iter().map(|s| s.id).collect::<Vec<_>>(), [1, 3]);
+    }
 }
@@ -1,10 +1,228 @@
-use crate::db;
-
-/// Sell an owned inventory item. Credits the user's balance by `price`
-/// and removes the inventory row. Returns the new balance on success.
-pub fn sell_item(db_path: &str, user_id: i64, inventory_id: i64, price: f64) -> Result<f64, String> {
-    // Credit the user's balance first, then remove the inventory item.
-    let new_bal = db::change_user_balance(db_path, user_id, price)?;
-    db::remove_inventory_item(db_path, inventory_id)?;
+use crate::db::Database;
+use crate::state::AppState;
+use crate::models::{LedgerKind, Money, OwnedSkin, Skin, Variant, Wear};
+use crate::scripts::market::MarketConfig;
+
+/// Market value of a copy of `skin` with the given float and variant: the live price
+/// scaled by its wear tier and variant.
+pub fn value_at(skin: &Skin, float_value: f64, variant: Variant) -> Money {
+    skin.price.scale(Wear::from_float(float_value).price_multiplier() * variant.price_multiplier())
+}
+
+/// Market value of an owned copy: the live price scaled by its wear tier and variant.
+pub fn item_value(item: &OwnedSkin) -> Money {
+    let base = item.skin.as_ref().map(|s| s.price).unwrap_or(Money::ZERO);
+    base.scale(item.inventory.wear.price_multiplier() * item.inventory.variant.price_multiplier())
+}
+
+/// Price offered for an owned copy: its `item_value` less the seller's side of the
+/// market spread.
+pub fn sale_price(item: &OwnedSkin, market: &MarketConfig) -> Money {
+    market.bid(item_value(item))
+}
+
+/// Sell an owned inventory item. Removes the inventory row and credits the user's
+/// balance with its `sale_price`, in one transaction; an item that does not exist
+/// or belongs to another user is rejected without crediting anything. The sale
+/// pushes the skin's live price down. Returns the new balance on success and
+/// invalidates the user's cached inventory and balance and the cached catalog prices.
+pub fn sell_item(
+    db: &Database,
+    state: &mut AppState,
+    market: &MarketConfig,
+    user_id: i64,
+    inventory_id: i64,
+) -> Result<Money, String> {
+    let tx = db.transaction()?;
+    let (_, new_bal) = sell_owned(db, market, user_id, inventory_id)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    state.invalidate_user();
+    state.invalidate_catalog();
     Ok(new_bal)
 }
+
+/// Outcome of `sell_items`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchSale {
+    pub sold: usize,
+    /// Sum of the sale prices credited.
+    pub total: Money,
+    /// Balance after the last credit.
+    pub balance: Money,
+}
+
+/// What `sell_items` credits for `items` sold in this order: each copy is priced
+/// after the sales before it have moved its skin's live price with
+/// `MarketConfig::next_price`, so the quote matches the batch's total.
+pub fn quote_batch<'a>(items: impl IntoIterator<Item = &'a OwnedSkin>, market: &MarketConfig) -> Money {
+    let mut live: std::collections::HashMap<i64, Money> = std::collections::HashMap::new();
+    let mut total = Money::ZERO;
+    for item in items {
+        let Some(skin) = &item.skin else { continue };
+        let price = live.entry(skin.id).or_insert(skin.price);
+        let value = price.scale(item.inventory.wear.price_multiplier() * item.inventory.variant.price_multiplier());
+        total += market.bid(value);
+        *price = market.next_price(*price, skin.base_price, LedgerKind::Sell);
+    }
+    total
+}
+
+/// Sell several owned items at once. Every item is sold in one transaction with its
+/// own ledger entry; if any of them does not exist or belongs to another user the
+/// whole batch is rolled back and nothing is credited. Each sale moves the live
+/// price before the next one, so selling many copies of a skin gets less for each.
+pub fn sell_items(
+    db: &Database,
+    state: &mut AppState,
+    market: &MarketConfig,
+    user_id: i64,
+    inventory_ids: &[i64],
+) -> Result<BatchSale, String> {
+    if inventory_ids.is_empty() {
+        return Err("No items selected".into());
+    }
+    let tx = db.transaction()?;
+    let mut sale = BatchSale { sold: 0, total: Money::ZERO, balance: Money::ZERO };
+    for &inventory_id in inventory_ids {
+        let (price, new_bal) = sell_owned(db, market, user_id, inventory_id)?;
+        sale.sold += 1;
+        sale.total += price;
+        sale.balance = new_bal;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    state.invalidate_user();
+    state.invalidate_catalog();
+    Ok(sale)
+}
+
+/// Remove one item, credit its price and move the skin's live price; returns the
+/// price and the new balance. Runs inside the caller's transaction.
+fn sell_owned(db: &Database, market: &MarketConfig, user_id: i64, inventory_id: i64) -> Result<(Money, Money), String> {
+    // Remove the item first: this is the ownership check
+    let item = db.take_inventory_item(user_id, inventory_id)?;
+    let price = sale_price(&item, market);
+    let new_bal = db.credit_balance(
+        user_id,
+        price,
+        LedgerKind::Sell,
+        Some(item.inventory.skin_id),
+        Some(item.inventory.id),
+    )?;
+    if let Some(skin) = &item.skin {
+        crate::scripts::market::record_trade(db, market, skin, LedgerKind::Sell)?;
+    }
+    Ok((price, new_bal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{balance, temp_db, user_and_skin};
+
+    #[test]
+    fn sell_credits_and_removes_item() {
+        let db = temp_db("sell_ok");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let item = db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap();
+
+        let new_bal = sell_item(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, item.id).unwrap();
+
+        assert_eq!(new_bal, Money::from_cents(11000));
+        assert!(db.get_inventory_for_user(user_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ledger_sums_to_balance() {
+        let db = temp_db("sell_ledger");
+        let (user_id, skin_id) = user_and_skin(&db);
+        crate::scripts::buy::attempt_buy(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, skin_id, Variant::Normal).unwrap();
+        let item = db.get_inventory_for_user(user_id).unwrap().remove(0);
+        sell_item(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, item.inventory.id).unwrap();
+
+        let entries = db.list_ledger(user_id, None).unwrap();
+        let kinds: Vec<LedgerKind> = entries.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [LedgerKind::Sell, LedgerKind::Buy, LedgerKind::Deposit]);
+        assert_eq!(entries[0].inventory_id, Some(item.inventory.id));
+        let total: Money = entries.iter().map(|e| e.amount).sum();
+        assert_eq!(total, balance(&db, user_id));
+
+        let sells = db.list_ledger(user_id, Some(LedgerKind::Sell)).unwrap();
+        assert_eq!(sells.len(), 1);
+    }
+
+    #[test]
+    fn sell_someone_elses_item_is_rejected() {
+        let db = temp_db("sell_foreign");
+        let (owner, skin_id) = user_and_skin(&db);
+        let thief = db.create_user(&Default::default(), "thief", "secret12").unwrap().id;
+        let item = db.add_inventory_item(owner, skin_id, 0.2, Variant::Normal).unwrap();
+
+        assert!(sell_item(&db, &mut AppState::default(), &MarketConfig::FIXED, thief, item.id).is_err());
+        assert!(db.list_ledger(thief, Some(LedgerKind::Sell)).unwrap().is_empty());
+
+        assert_eq!(balance(&db, thief), Money::from_cents(10000));
+        assert_eq!(db.get_inventory_for_user(owner).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn selling_twice_credits_once() {
+        let db = temp_db("sell_stale");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let item = db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap();
+
+        sell_item(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, item.id).unwrap();
+        assert!(sell_item(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, item.id).is_err());
+
+        assert_eq!(balance(&db, user_id), Money::from_cents(11000));
+    }
+
+    #[test]
+    fn batch_sell_is_all_or_nothing() {
+        let db = temp_db("sell_batch");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let other = db.create_user(&Default::default(), "other", "secret12").unwrap().id;
+        let mine: Vec<i64> = (0..3)
+            .map(|_| db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap().id)
+            .collect();
+        let theirs = db.add_inventory_item(other, skin_id, 0.2, Variant::Normal).unwrap().id;
+
+        // One foreign item rolls back the items sold before it
+        let mut ids = mine.clone();
+        ids.insert(1, theirs);
+        assert!(sell_items(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, &ids).is_err());
+        assert_eq!(db.get_inventory_for_user(user_id).unwrap().len(), 3);
+        assert!(db.list_ledger(user_id, Some(LedgerKind::Sell)).unwrap().is_empty());
+        assert_eq!(balance(&db, user_id), Money::from_cents(10000));
+
+        let sale = sell_items(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, &mine).unwrap();
+        assert_eq!(sale, BatchSale { sold: 3, total: Money::from_cents(3000), balance: Money::from_cents(13000) });
+        assert!(db.get_inventory_for_user(user_id).unwrap().is_empty());
+        assert_eq!(db.list_ledger(user_id, Some(LedgerKind::Sell)).unwrap().len(), 3);
+        assert!(sell_items(&db, &mut AppState::default(), &MarketConfig::FIXED, user_id, &[]).is_err());
+    }
+
+    #[test]
+    fn batch_quote_matches_what_the_batch_credits() {
+        let db = temp_db("sell_quote");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let other_skin = db
+            .add_skin("Test | Other", Some(crate::models::Rarity::MilSpec), Money::from_cents(777), None, None, None, (0.0, 1.0))
+            .unwrap()
+            .id;
+        db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap();
+        db.add_inventory_item(user_id, other_skin, 0.01, Variant::StatTrak).unwrap();
+        db.add_inventory_item(user_id, skin_id, 0.5, Variant::Normal).unwrap();
+        db.add_inventory_item(user_id, skin_id, 0.1, Variant::Souvenir).unwrap();
+        let market = MarketConfig::default();
+
+        let items = db.get_inventory_for_user(user_id).unwrap();
+        let ids: Vec<i64> = items.iter().map(|it| it.inventory.id).collect();
+        let quote = quote_batch(&items, &market);
+        // Pricing every copy at today's price would promise more than the batch pays
+        let naive: Money = items.iter().map(|it| sale_price(it, &market)).sum();
+        assert!(quote < naive);
+
+        let sale = sell_items(&db, &mut AppState::default(), &market, user_id, &ids).unwrap();
+        assert_eq!(sale.total, quote);
+    }
+}
@@ -4,112 +4,335 @@
 /// The helpers here perform the core logic (validation, DB changes) and
 /// return results suitable for the UI to present.
 use rand::prelude::*;
-use rusqlite::{params, Connection};
-
-fn canonical_rarity(s: &str) -> String {
-    let s = s.to_lowercase();
-    if s.contains("consumer") || s.contains("common") { "consumer".into() }
-    else if s.contains("industrial") || s.contains("industrial grade") { "industrial".into() }
-    else if s.contains("mil-spec") || s.contains("milspec") || s.contains("mil") { "mil-spec".into() }
-    else if s.contains("restricted") { "restricted".into() }
-    else if s.contains("classified") { "classified".into() }
-    else if s.contains("covert") { "covert".into() }
-    else if s.contains("rare special") || s.contains("rare") { "rare special".into() }
-    else { s }
-}
-
-/// Compose a tradeup: consume exactly 10 inventory items owned by `user_id` (their inventory IDs),
-/// all must have the same canonical rarity. Produces one random skin of the next-higher
-/// canonical rarity. Knife/rare-special items are excluded from being produced.
-pub fn compose_tradeup(db_path: &str, user_id: i64, input_item_ids: Vec<i64>) -> Result<i64, String> {
-    if input_item_ids.len() != 10 {
-        return Err("Tradeup requires exactly 10 items".into());
-    }
-
-    // Load the user's inventory to validate ownership and gather rarities
-    let owned = crate::db::get_inventory_for_user(db_path, user_id)?;
-    let mut by_id = std::collections::HashMap::new();
-    for it in owned {
-        by_id.insert(it.inventory.id, it);
+use rusqlite::params;
+use crate::db::Database;
+use crate::state::AppState;
+use crate::models::Rarity;
+
+/// Kind of trade-up contract. Each kind has its own input count, validation and
+/// candidate pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContractKind {
+    /// 10 items of one rarity up to Covert → one item of the next rarity, never a knife.
+    #[default]
+    Standard,
+    /// 5 Covert items → one knife or pair of gloves (rare special) from the inputs' cases.
+    CovertToGold,
+}
+
+impl ContractKind {
+    pub const ALL: [ContractKind; 2] = [ContractKind::Standard, ContractKind::CovertToGold];
+
+    /// Number of items the contract consumes.
+    pub fn input_count(&self) -> usize {
+        match self {
+            ContractKind::Standard => 10,
+            ContractKind::CovertToGold => 5,
+        }
     }
 
-    let mut rarities = Vec::new();
-    let mut skins_in = Vec::new();
-    for id in &input_item_ids {
-        let entry = by_id.get(id).ok_or_else(|| format!("You do not own inventory item {}", id))?;
-        let skin = entry.skin.as_ref().ok_or_else(|| format!("Inventory item {} has no skin metadata", id))?;
-        rarities.push(canonical_rarity(&skin.rarity.clone().unwrap_or_default()));
-        skins_in.push((entry.inventory.id, skin.clone()));
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContractKind::Standard => "Standard (10 items)",
+            ContractKind::CovertToGold => "Covert → Gold (5 items)",
+        }
+    }
+}
+
+/// A contract input: an owned copy's float and variant together with its catalog skin.
+#[derive(Debug, Clone, Copy)]
+pub struct TradeupInput<'a> {
+    pub float_value: f64,
+    pub variant: crate::models::Variant,
+    pub skin: &'a crate::models::Skin,
+}
+
+impl<'a> TradeupInput<'a> {
+    /// Build an input from an inventory entry; `None` if the entry has no catalog skin.
+    pub fn from_owned(owned: &'a crate::models::OwnedSkin) -> Option<Self> {
+        owned.skin.as_ref().map(|skin| TradeupInput {
+            float_value: owned.inventory.float_value,
+            variant: owned.inventory.variant,
+            skin,
+        })
+    }
+}
+
+/// Outcome of a completed tradeup: the newly created inventory row, its catalog skin
+/// and the full distribution the result was drawn from.
+#[derive(Debug, Clone)]
+pub struct TradeupResult {
+    pub item: crate::models::InventoryItem,
+    pub skin: crate::models::Skin,
+    pub outcomes: Vec<TradeupOutcome>,
+}
+
+/// Position of `float_value` inside `skin`'s float range, scaled to 0.0–1.0.
+pub fn normalized_float(float_value: f64, skin: &crate::models::Skin) -> f64 {
+    let span = skin.max_float - skin.min_float;
+    if span <= 0.0 {
+        return 0.0;
+    }
+    ((float_value - skin.min_float) / span).clamp(0.0, 1.0)
+}
+
+/// Average normalized float of the contract inputs.
+pub fn average_normalized_float(inputs: &[TradeupInput]) -> f64 {
+    if inputs.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = inputs.iter().map(|i| normalized_float(i.float_value, i.skin)).sum();
+    sum / inputs.len() as f64
+}
+
+/// CS contract formula: map the inputs' average normalized float onto the
+/// output skin's float range.
+pub fn output_float(inputs: &[TradeupInput], output: &crate::models::Skin) -> f64 {
+    let avg = average_normalized_float(inputs);
+    output.min_float + avg * (output.max_float - output.min_float)
+}
+
+/// One possible result of a tradeup contract.
+#[derive(Debug, Clone)]
+pub struct TradeupOutcome {
+    pub skin: crate::models::Skin,
+    /// Chance (0.0–1.0) that this skin is produced.
+    pub probability: f64,
+    /// Float the output would have, from `output_float`.
+    pub float_value: f64,
+    /// Variant the output inherits from the inputs.
+    pub variant: crate::models::Variant,
+}
+
+/// Knives are never produced by a regular contract: weapon_type contains "knife"
+/// or the name carries the special star.
+fn is_knife(skin: &crate::models::Skin) -> bool {
+    let name_lower = skin.name.to_lowercase();
+    let weapon_lower = skin.weapon_type.clone().unwrap_or_default().to_lowercase();
+    weapon_lower.contains("knife") || name_lower.contains('★') || name_lower.contains("knife")
+}
+
+/// Validate the inputs' variants and return the variant the output will have.
+/// Souvenirs cannot be used in contracts, and StatTrak™ and non-StatTrak™ inputs
+/// cannot be mixed; a StatTrak™ contract produces a StatTrak™ item.
+fn contract_variant(inputs: &[TradeupInput]) -> Result<crate::models::Variant, String> {
+    use crate::models::Variant;
+    if inputs.iter().any(|i| i.variant == Variant::Souvenir) {
+        return Err("Souvenir items cannot be used in a tradeup".into());
     }
+    let stattrak = inputs.iter().filter(|i| i.variant == Variant::StatTrak).count();
+    if stattrak == 0 {
+        Ok(Variant::Normal)
+    } else if stattrak == inputs.len() {
+        Ok(Variant::StatTrak)
+    } else {
+        Err("StatTrak™ and non-StatTrak™ items cannot be mixed in a tradeup".into())
+    }
+}
+
+/// Validate that all inputs share one rarity allowed for `kind` and return the
+/// rarity they trade up into.
+fn target_rarity(inputs: &[TradeupInput], kind: ContractKind) -> Result<Rarity, String> {
+    let first = inputs.first().ok_or_else(|| "Tradeup has no inputs".to_string())?;
+    let current_rarity = first
+        .skin
+        .rarity
+        .ok_or_else(|| format!("'{}' has no rarity and cannot be traded up", first.skin.name))?;
 
     // Ensure all rarities are identical
-    if rarities.iter().any(|r| r != &rarities[0]) {
+    if inputs.iter().any(|i| i.skin.rarity != Some(current_rarity)) {
         return Err("All items must be the same rarity to trade up".into());
     }
 
-    let current_rarity = rarities[0].clone();
-
-    // Define rarity progression (low -> high)
-    let rarity_order = vec![
-        "consumer",
-        "industrial",
-        "mil-spec",
-        "restricted",
-        "classified",
-        "covert",
-        "rare special",
-    ];
-
-    let pos = rarity_order.iter().position(|&r| r == current_rarity.as_str());
-    let idx = match pos {
-        Some(i) => i,
-        None => return Err(format!("Rarity '{}' cannot be traded up", current_rarity)),
-    };
-
-    if idx + 1 >= rarity_order.len() {
-        return Err("No higher rarity available to trade up to".into());
-    }
-
-    let target_rarity = rarity_order[idx + 1];
-
-    // Load candidate skins from catalog with target rarity, excluding knives / special rare knives
-    let candidates_all = crate::db::list_skins(db_path)?;
-    let candidates: Vec<crate::models::Skin> = candidates_all
-        .into_iter()
-        .filter(|s| {
-            let r = s.rarity.clone().unwrap_or_default();
-            let rcanon = canonical_rarity(&r);
-            if rcanon != target_rarity { return false; }
-            // Exclude knives: weapon_type contains "knife" or name contains special star
-            let name_lower = s.name.to_lowercase();
-            let weapon_lower = s.weapon_type.clone().unwrap_or_default().to_lowercase();
-            if weapon_lower.contains("knife") || name_lower.contains('★') || name_lower.contains("knife") {
-                return false;
+    let target = current_rarity
+        .next()
+        .ok_or_else(|| "No higher rarity available to trade up to".to_string())?;
+    match kind {
+        ContractKind::Standard if target == Rarity::RareSpecial => {
+            Err("Covert items trade up through the 5-item Covert → Gold contract".into())
+        }
+        ContractKind::CovertToGold if target != Rarity::RareSpecial => {
+            Err("The Covert → Gold contract only accepts Covert items".into())
+        }
+        _ => Ok(target),
+    }
+}
+
+/// Compute every possible output of a contract and its probability, without touching the DB.
+///
+/// Outcomes are weighted by input collection the way CS does it: each input
+/// contributes an equal share of the odds, split evenly across the next-rarity
+/// skins of its own collection. An input whose collection has no next-rarity
+/// skins in `catalog` makes the contract invalid. Standard contracts never
+/// produce knives; Covert → Gold contracts draw from the rare special pool of
+/// every case in `cases` that drops the input's collection (or the input's own
+/// collection when no case does).
+pub fn outcome_distribution(
+    inputs: &[TradeupInput],
+    catalog: &[crate::models::Skin],
+    cases: &[crate::models::Case],
+    kind: ContractKind,
+) -> Result<Vec<TradeupOutcome>, String> {
+    if inputs.len() > kind.input_count() {
+        return Err(format!("This contract takes at most {} items", kind.input_count()));
+    }
+    let target = target_rarity(inputs, kind)?;
+    let variant = contract_variant(inputs)?;
+    let share = 1.0 / inputs.len() as f64;
+
+    let mut outcomes: Vec<TradeupOutcome> = Vec::new();
+    for input in inputs.iter().map(|i| i.skin) {
+        let collection = input
+            .collection
+            .as_deref()
+            .ok_or_else(|| format!("'{}' has no collection and cannot be traded up", input.name))?;
+
+        // Collections the output may come from
+        let mut sources: Vec<&str> = vec![collection];
+        if kind == ContractKind::CovertToGold {
+            let from_cases: Vec<&str> = cases
+                .iter()
+                .filter(|c| c.collections.iter().any(|col| col == collection))
+                .flat_map(|c| c.rare_special_collections.iter().map(String::as_str))
+                .collect();
+            if !from_cases.is_empty() {
+                sources = from_cases;
             }
-            true
-        })
-        .collect();
+        }
+
+        // Candidate skins: source collection and target rarity. Standard contracts
+        // exclude knives / special rare knives.
+        let pool: Vec<&crate::models::Skin> = catalog
+            .iter()
+            .filter(|s| s.collection.as_deref().is_some_and(|c| sources.contains(&c)))
+            .filter(|s| s.rarity == Some(target))
+            .filter(|s| kind == ContractKind::CovertToGold || !is_knife(s))
+            .collect();
+
+        if pool.is_empty() {
+            return Err(format!(
+                "No {} skins in the '{}' collection for '{}' to trade up into",
+                target.label(), collection, input.name
+            ));
+        }
+
+        let per_skin = share / pool.len() as f64;
+        for skin in pool {
+            if let Some(existing) = outcomes.iter_mut().find(|o| o.skin.id == skin.id) {
+                existing.probability += per_skin;
+            } else {
+                outcomes.push(TradeupOutcome {
+                    skin: skin.clone(),
+                    probability: per_skin,
+                    float_value: output_float(inputs, skin),
+                    variant,
+                });
+            }
+        }
+    }
+
+    // Most likely outcomes first
+    outcomes.sort_by(|a, b| b.probability.total_cmp(&a.probability).then_with(|| a.skin.name.cmp(&b.skin.name)));
+    Ok(outcomes)
+}
+
+/// Expected-value breakdown of a contract, used to preview it before committing.
+#[derive(Debug, Clone)]
+pub struct ContractStats {
+    pub outcomes: Vec<TradeupOutcome>,
+    /// Combined value of the inputs being consumed.
+    pub input_cost: crate::models::Money,
+    /// Probability-weighted value of the output, rounded to the cent.
+    pub expected_value: crate::models::Money,
+    /// `expected_value - input_cost`.
+    pub expected_profit: crate::models::Money,
+    /// Chance that the output is worth more than the inputs.
+    pub profit_chance: f64,
+}
+
+/// Compute odds, expected value and profit chance for a contract without touching the DB.
+/// Inputs and outputs are valued at their market value (`sell::value_at`), before the spread.
+pub fn contract_stats(
+    inputs: &[TradeupInput],
+    catalog: &[crate::models::Skin],
+    cases: &[crate::models::Case],
+    kind: ContractKind,
+) -> Result<ContractStats, String> {
+    let outcomes = outcome_distribution(inputs, catalog, cases, kind)?;
+    let input_cost: crate::models::Money = inputs
+        .iter()
+        .map(|i| crate::scripts::sell::value_at(i.skin, i.float_value, i.variant))
+        .sum();
+
+    let mut expected_cents = 0.0;
+    let mut profit_chance = 0.0;
+    for o in &outcomes {
+        let value = crate::scripts::sell::value_at(&o.skin, o.float_value, o.variant);
+        expected_cents += o.probability * value.cents() as f64;
+        if value > input_cost {
+            profit_chance += o.probability;
+        }
+    }
+    let expected_value = crate::models::Money::from_cents(expected_cents.round() as i64);
+
+    Ok(ContractStats {
+        outcomes,
+        input_cost,
+        expected_value,
+        expected_profit: expected_value - input_cost,
+        profit_chance,
+    })
+}
+
+/// Compose a tradeup: consume exactly `kind.input_count()` inventory items owned by `user_id`
+/// (their inventory IDs), all must have the same canonical rarity. Produces one skin of the
+/// next-higher canonical rarity, drawn from `outcome_distribution`, whose float is derived
+/// from the inputs with `output_float`. Only `ContractKind::CovertToGold` can produce
+/// knife/rare-special items. Outcomes are drawn from the cached catalog in `state`, and
+/// a completed contract invalidates the user's cached inventory.
+pub fn compose_tradeup(
+    db: &Database,
+    state: &mut AppState,
+    user_id: i64,
+    input_item_ids: Vec<i64>,
+    kind: ContractKind,
+) -> Result<TradeupResult, String> {
+    if input_item_ids.len() != kind.input_count() {
+        return Err(format!("Tradeup requires exactly {} items", kind.input_count()));
+    }
+
+    // Load the user's inventory to validate ownership and gather input skins
+    let owned = db.get_inventory_for_user(user_id)?;
+    let mut by_id = std::collections::HashMap::new();
+    for it in owned {
+        by_id.insert(it.inventory.id, it);
+    }
 
-    if candidates.is_empty() {
-        return Err(format!("No candidate skins found for target rarity '{}'", target_rarity));
+    let mut inputs = Vec::new();
+    for id in &input_item_ids {
+        let entry = by_id.get(id).ok_or_else(|| format!("You do not own inventory item {}", id))?;
+        inputs.push(TradeupInput::from_owned(entry).ok_or_else(|| format!("Inventory item {} has no skin metadata", id))?);
     }
 
-    // Pick a random candidate uniformly
+    let catalog = state.catalog(db)?;
+    let cases = state.cases(db)?;
+    let outcomes = outcome_distribution(&inputs, &catalog, &cases, kind)?;
+
+    // Pick a candidate according to the collection-weighted odds
     let mut rng = thread_rng();
-    let selected = candidates.choose(&mut rng).unwrap().clone();
+    let picked = outcomes
+        .choose_weighted(&mut rng, |o| o.probability)
+        .map_err(|e| e.to_string())?;
+    let selected = picked.skin.clone();
+    let float_value = picked.float_value;
+    let variant = picked.variant;
 
     // Perform DB transaction: insert resulting inventory row and delete consumed items
-    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let tx = db.transaction()?;
 
-    tx.execute(
-        "INSERT INTO inventory (user_id, skin_id) VALUES (?1, ?2)",
-        params![user_id, selected.id],
-    )
-    .map_err(|e| e.to_string())?;
-    let new_id = tx.last_insert_rowid();
+    let item = db.add_inventory_item(user_id, selected.id, float_value, variant)?;
 
-    for (inv_id, _skin) in skins_in.iter() {
+    for inv_id in &input_item_ids {
         let changes = tx
             .execute("DELETE FROM inventory WHERE id = ?1 AND user_id = ?2", params![inv_id, user_id])
             .map_err(|e| e.to_string())?;
@@ -119,7 +342,264 @@ pub fn compose_tradeup(db_path: &str, user_id: i64, input_item_ids: Vec<i64>) ->
         }
     }
 
+    // No money moves, but the contract is still part of the audit trail
+    db.record_ledger(
+        user_id,
+        crate::models::Money::ZERO,
+        crate::models::LedgerKind::Tradeup,
+        Some(selected.id),
+        Some(item.id),
+    )?;
+
     tx.commit().map_err(|e| e.to_string())?;
+    state.invalidate_user();
+
+    Ok(TradeupResult {
+        item,
+        skin: selected,
+        outcomes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{temp_db, user_and_skin};
+    use crate::models::{Case, Money, Skin, Variant};
+
+    fn skin(id: i64, rarity: Rarity, collection: &str, floats: (f64, f64)) -> Skin {
+        Skin {
+            id,
+            name: format!("Test | Skin {}", id),
+            rarity: Some(rarity),
+            price: Money::from_cents(1000),
+            base_price: Money::from_cents(1000),
+            collection: Some(collection.to_string()),
+            weapon_type: Some("Rifle".to_string()),
+            has_image: false,
+            min_float: floats.0,
+            max_float: floats.1,
+        }
+    }
+
+    fn input(skin: &Skin, float_value: f64) -> TradeupInput<'_> {
+        TradeupInput { float_value, variant: Variant::Normal, skin }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn output_float_maps_the_average_normalized_float_into_the_output_range() {
+        let full = skin(1, Rarity::MilSpec, "A", (0.0, 1.0));
+        let narrow = skin(2, Rarity::MilSpec, "A", (0.1, 0.5));
+        let output = skin(3, Rarity::Restricted, "A", (0.06, 0.8));
+
+        // 0.25 of the full range and 0.3 = halfway through 0.1–0.5 average to 0.375
+        let inputs = [input(&full, 0.25), input(&narrow, 0.3)];
+        assert_close(average_normalized_float(&inputs), 0.375);
+        assert_close(output_float(&inputs, &output), 0.06 + 0.375 * 0.74);
+    }
+
+    #[test]
+    fn floats_outside_the_skin_range_are_clamped() {
+        let narrow = skin(1, Rarity::MilSpec, "A", (0.1, 0.5));
+        let output = skin(2, Rarity::Restricted, "A", (0.0, 0.5));
+
+        assert_eq!(normalized_float(0.9, &narrow), 1.0);
+        assert_eq!(normalized_float(0.05, &narrow), 0.0);
+        assert_close(output_float(&[input(&narrow, 0.9)], &output), 0.5);
+    }
+
+    #[test]
+    fn fixed_float_skins_do_not_divide_by_zero() {
+        let fixed = skin(1, Rarity::MilSpec, "A", (0.3, 0.3));
+        let output = skin(2, Rarity::Restricted, "A", (0.2, 0.2));
+
+        assert_eq!(normalized_float(0.3, &fixed), 0.0);
+        assert_eq!(output_float(&[input(&fixed, 0.3)], &output), 0.2);
+        assert_close(output_float(&[input(&fixed, 0.3)], &skin(3, Rarity::Restricted, "A", (0.0, 1.0))), 0.0);
+    }
+
+    #[test]
+    fn odds_are_weighted_by_input_collection() {
+        let in_a = skin(1, Rarity::MilSpec, "A", (0.0, 1.0));
+        let in_b = skin(2, Rarity::MilSpec, "B", (0.0, 1.0));
+        let catalog = [
+            in_a.clone(),
+            in_b.clone(),
+            skin(10, Rarity::Restricted, "A", (0.0, 1.0)),
+            skin(20, Rarity::Restricted, "B", (0.0, 1.0)),
+            skin(21, Rarity::Restricted, "B", (0.0, 1.0)),
+            skin(22, Rarity::Restricted, "B", (0.0, 1.0)),
+            // Wrong tier, never an outcome
+            skin(30, Rarity::Classified, "A", (0.0, 1.0)),
+        ];
+        let mut inputs = vec![input(&in_a, 0.2); 7];
+        inputs.extend([input(&in_b, 0.2); 3]);
 
-    Ok(new_id)
+        let outcomes = outcome_distribution(&inputs, &catalog, &[], ContractKind::Standard).unwrap();
+        let shares: Vec<(i64, f64)> = outcomes.iter().map(|o| (o.skin.id, o.probability)).collect();
+        assert_eq!(shares.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [10, 20, 21, 22]);
+        for ((_, share), expected) in shares.iter().zip([0.7, 0.1, 0.1, 0.1]) {
+            assert_close(*share, expected);
+        }
+    }
+
+    #[test]
+    fn contract_variant_follows_the_inputs() {
+        let s = skin(1, Rarity::MilSpec, "A", (0.0, 1.0));
+        let with = |variant| TradeupInput { variant, ..input(&s, 0.2) };
+
+        assert_eq!(contract_variant(&[with(Variant::StatTrak), with(Variant::StatTrak)]), Ok(Variant::StatTrak));
+        assert_eq!(contract_variant(&[with(Variant::Normal), with(Variant::Normal)]), Ok(Variant::Normal));
+        assert_eq!(
+            contract_variant(&[with(Variant::Normal), with(Variant::Souvenir)]).unwrap_err(),
+            "Souvenir items cannot be used in a tradeup"
+        );
+        assert_eq!(
+            contract_variant(&[with(Variant::StatTrak), with(Variant::Normal)]).unwrap_err(),
+            "StatTrak™ and non-StatTrak™ items cannot be mixed in a tradeup"
+        );
+    }
+
+    #[test]
+    fn contract_stats_value_inputs_and_outcomes() {
+        let cheap_input = Skin { price: Money::from_cents(100), ..skin(1, Rarity::MilSpec, "A", (0.0, 1.0)) };
+        let catalog = [
+            cheap_input.clone(),
+            Skin { price: Money::from_cents(100), ..skin(10, Rarity::Restricted, "A", (0.0, 1.0)) },
+            Skin { price: Money::from_cents(500), ..skin(11, Rarity::Restricted, "A", (0.0, 1.0)) },
+        ];
+        // Two Field-Tested inputs at $1.00; both outcomes come out Field-Tested too
+        let inputs = [input(&cheap_input, 0.2), input(&cheap_input, 0.2)];
+
+        let stats = contract_stats(&inputs, &catalog, &[], ContractKind::Standard).unwrap();
+        assert_close(stats.outcomes.iter().map(|o| o.probability).sum(), 1.0);
+        assert_eq!(stats.input_cost, Money::from_cents(200));
+        assert_eq!(stats.expected_value, Money::from_cents(300));
+        assert_eq!(stats.expected_profit, Money::from_cents(100));
+        // Only the $5.00 outcome beats the $2.00 put in
+        assert_close(stats.profit_chance, 0.5);
+    }
+
+    #[test]
+    fn invalid_contracts_are_rejected() {
+        let mil_spec = skin(1, Rarity::MilSpec, "A", (0.0, 1.0));
+        let restricted = skin(2, Rarity::Restricted, "A", (0.0, 1.0));
+        let dead_end = skin(3, Rarity::MilSpec, "C", (0.0, 1.0));
+        let catalog = [mil_spec.clone(), restricted.clone(), dead_end.clone(), skin(4, Rarity::Classified, "A", (0.0, 1.0))];
+
+        let mixed = [input(&mil_spec, 0.2), input(&restricted, 0.2)];
+        assert_eq!(
+            outcome_distribution(&mixed, &catalog, &[], ContractKind::Standard).unwrap_err(),
+            "All items must be the same rarity to trade up"
+        );
+
+        // Collection C has no Restricted skins to trade up into
+        let stranded = [input(&mil_spec, 0.2), input(&dead_end, 0.2)];
+        let err = outcome_distribution(&stranded, &catalog, &[], ContractKind::Standard).unwrap_err();
+        assert_eq!(err, "No Restricted skins in the 'C' collection for 'Test | Skin 3' to trade up into");
+    }
+
+    #[test]
+    fn contract_kinds_only_take_their_own_rarity() {
+        let covert = skin(1, Rarity::Covert, "A", (0.0, 1.0));
+        let classified = skin(2, Rarity::Classified, "A", (0.0, 1.0));
+
+        assert_eq!(
+            target_rarity(&[input(&covert, 0.2)], ContractKind::Standard).unwrap_err(),
+            "Covert items trade up through the 5-item Covert → Gold contract"
+        );
+        assert_eq!(target_rarity(&[input(&classified, 0.2)], ContractKind::Standard), Ok(Rarity::Covert));
+        assert_eq!(
+            target_rarity(&[input(&classified, 0.2)], ContractKind::CovertToGold).unwrap_err(),
+            "The Covert → Gold contract only accepts Covert items"
+        );
+        assert_eq!(target_rarity(&[input(&covert, 0.2)], ContractKind::CovertToGold), Ok(Rarity::RareSpecial));
+    }
+
+    #[test]
+    fn gold_comes_from_the_cases_rare_special_collections() {
+        let covert = skin(1, Rarity::Covert, "A", (0.0, 1.0));
+        let knife = skin(2, Rarity::RareSpecial, "Knives", (0.0, 1.0));
+        let gloves = skin(3, Rarity::RareSpecial, "Gloves", (0.0, 1.0));
+        let catalog = [
+            covert.clone(),
+            knife.clone(),
+            gloves.clone(),
+            // Gold outside the case's rare special collections never comes out
+            skin(4, Rarity::RareSpecial, "A", (0.0, 1.0)),
+            skin(5, Rarity::RareSpecial, "Other Knives", (0.0, 1.0)),
+        ];
+        let case = Case {
+            id: 1,
+            name: "Test Case".to_string(),
+            price: Money::ZERO,
+            key_price: Money::ZERO,
+            image_base64: None,
+            collections: vec!["A".to_string()],
+            rare_special_collections: vec!["Knives".to_string(), "Gloves".to_string()],
+            odds: Rarity::DEFAULT_CASE_ODDS.to_vec(),
+        };
+
+        let inputs = [input(&covert, 0.2); 5];
+        let outcomes = outcome_distribution(&inputs, &catalog, &[case], ContractKind::CovertToGold).unwrap();
+        let ids: Vec<i64> = outcomes.iter().map(|o| o.skin.id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert_close(outcomes[0].probability, 0.5);
+    }
+
+    /// A user owning `count` Mil-Spec copies in a collection with one Restricted skin.
+    fn contract_owner(db: &Database, count: usize) -> (i64, Vec<i64>) {
+        let (user_id, skin_id) = user_and_skin(db);
+        db.add_skin("Test | Upgrade", Some(Rarity::Restricted), Money::from_cents(5000), Some("Test Collection"), None, None, (0.0, 1.0)).unwrap();
+        let ids = (0..count).map(|_| db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap().id).collect();
+        (user_id, ids)
+    }
+
+    #[test]
+    fn compose_tradeup_consumes_owned_items() {
+        let db = temp_db("tradeup_ok");
+        let (user_id, ids) = contract_owner(&db, 10);
+
+        let result = compose_tradeup(&db, &mut AppState::default(), user_id, ids, ContractKind::Standard).unwrap();
+
+        assert_eq!(result.skin.name, "Test | Upgrade");
+        let inventory = db.get_inventory_for_user(user_id).unwrap();
+        assert_eq!(inventory.iter().map(|it| it.inventory.id).collect::<Vec<_>>(), [result.item.id]);
+    }
+
+    #[test]
+    fn compose_tradeup_rejects_items_of_other_users() {
+        let db = temp_db("tradeup_not_owned");
+        let (user_id, mut ids) = contract_owner(&db, 9);
+        let other = db.create_user(&Default::default(), "other", "secret12").unwrap().id;
+        let skin_id = db.get_skin_by_name("Test | Skin").unwrap().unwrap().id;
+        let theirs = db.add_inventory_item(other, skin_id, 0.2, Variant::Normal).unwrap().id;
+        ids.push(theirs);
+
+        let err = compose_tradeup(&db, &mut AppState::default(), user_id, ids, ContractKind::Standard).unwrap_err();
+
+        assert_eq!(err, format!("You do not own inventory item {}", theirs));
+        assert_eq!(db.get_inventory_for_user(user_id).unwrap().len(), 9);
+        assert_eq!(db.get_inventory_for_user(other).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn compose_tradeup_with_duplicate_ids_rolls_back() {
+        let db = temp_db("tradeup_duplicates");
+        let (user_id, mut ids) = contract_owner(&db, 9);
+        ids.push(ids[0]);
+        let ledger_before = db.ledger_totals(user_id).unwrap();
+
+        assert!(compose_tradeup(&db, &mut AppState::default(), user_id, ids.clone(), ContractKind::Standard).is_err());
+
+        let mut left: Vec<i64> = db.get_inventory_for_user(user_id).unwrap().iter().map(|it| it.inventory.id).collect();
+        left.sort();
+        assert_eq!(left, ids[..9]);
+        assert_eq!(db.ledger_totals(user_id).unwrap(), ledger_before);
+        assert!(db.list_ledger(user_id, Some(crate::models::LedgerKind::Tradeup)).unwrap().is_empty());
+    }
 }
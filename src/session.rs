@@ -0,0 +1,113 @@
+//! "Remember me" sessions. Logging in with the box ticked creates a row in the
+//! `sessions` table and writes its token to a file next to the database; the next
+//! launch reads the file and logs the user back in while the session is valid.
+
+use crate::db::Database;
+use rand::RngCore;
+use std::path::PathBuf;
+
+/// How long a remembered login stays valid.
+pub const SESSION_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+const TOKEN_FILE: &str = "session.token";
+
+/// A fresh random session token (32 bytes, hex encoded).
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The token file lives in the same directory as the database.
+pub fn token_path(db_path: &str) -> PathBuf {
+    PathBuf::from(db_path).with_file_name(TOKEN_FILE)
+}
+
+/// Write `token` to the token file, readable only by the current user where supported.
+pub fn save_token(db_path: &str, token: &str) -> Result<(), String> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(token_path(db_path)).map_err(|e| e.to_string())?;
+    file.write_all(token.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// The remembered token, if there is one.
+pub fn load_token(db_path: &str) -> Option<String> {
+    let token = std::fs::read_to_string(token_path(db_path)).ok()?;
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+/// Forget the remembered token. A missing file is not an error.
+pub fn clear_token(db_path: &str) {
+    let _ = std::fs::remove_file(token_path(db_path));
+}
+
+/// Log the remembered user back in. Returns the user and their token when the
+/// token file names a valid, unexpired session; a stale token file is removed.
+pub fn restore(db: &Database) -> Option<(crate::models::User, String)> {
+    let token = load_token(db.path())?;
+    match db.session_user(&token) {
+        Ok(Some(user)) => Some((user, token)),
+        Ok(None) => {
+            clear_token(db.path());
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Log `user_id` in on this machine: create a session and remember its token.
+pub fn remember(db: &Database, user_id: i64) -> Result<String, String> {
+    let token = db.create_session(user_id, SESSION_TTL_SECS)?;
+    if let Err(e) = save_token(db.path(), &token) {
+        let _ = db.revoke_session(&token);
+        return Err(e);
+    }
+    Ok(token)
+}
+
+/// Log out: revoke the session in the database and remove the token file.
+pub fn forget(db: &Database, token: &str) {
+    let _ = db.revoke_session(token);
+    clear_token(db.path());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembered_login_is_restored_until_logout() {
+        let dir = std::env::temp_dir().join(format!("cs_trade_up_test_session_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("cs_trade_up.db").to_string_lossy().into_owned();
+        let db = Database::open(&db_path).unwrap();
+        let (user_id, _) = crate::db::test_support::user_and_skin(&db);
+
+        assert!(restore(&db).is_none());
+
+        let token = remember(&db, user_id).unwrap();
+        assert_eq!(token.len(), 64);
+        assert!(token_path(&db_path).exists());
+        let (user, restored) = restore(&db).unwrap();
+        assert_eq!((user.id, restored.as_str()), (user_id, token.as_str()));
+
+        forget(&db, &token);
+        assert!(!token_path(&db_path).exists());
+        assert!(db.session_user(&token).unwrap().is_none());
+
+        // A token file for a revoked session is dropped on restore
+        save_token(&db_path, &token).unwrap();
+        assert!(restore(&db).is_none());
+        assert!(!token_path(&db_path).exists());
+    }
+}
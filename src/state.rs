@@ -0,0 +1,162 @@
+//! In-memory copies of what the screens draw every frame: the skin catalog, the
+//! cases, and the logged-in user's inventory and balance. Each is loaded from the
+//! database the first time a screen asks for it and kept until a mutation in
+//! `scripts` (or the Refresh button) invalidates it.
+
+use crate::db::Database;
+use crate::models::{Case, InventoryValue, Money, OwnedSkin, Skin, SkinFilter};
+use std::rc::Rc;
+
+type DbResult<T> = std::result::Result<T, String>;
+
+/// How many of the most valuable copies `inventory_value` lists.
+const TOP_ITEMS: usize = 5;
+
+/// Cached app state held by `CsApp`. Lists are handed out as `Rc`s so a screen can
+/// keep iterating one while it calls a script that invalidates the cache.
+#[derive(Default)]
+pub struct AppState {
+    catalog: Option<Rc<Vec<Skin>>>,
+    cases: Option<Rc<Vec<Case>>>,
+    /// The last catalog search and its results.
+    search: Option<(SkinFilter, Rc<Vec<Skin>>)>,
+    collections: Option<Rc<Vec<String>>>,
+    weapon_types: Option<Rc<Vec<String>>>,
+    user: Option<UserState>,
+}
+
+/// What is cached for one user; switching users starts over.
+struct UserState {
+    user_id: i64,
+    inventory: Option<Rc<Vec<OwnedSkin>>>,
+    value: Option<Rc<InventoryValue>>,
+    balance: Option<Money>,
+}
+
+impl AppState {
+    /// The whole skin catalog.
+    pub fn catalog(&mut self, db: &Database) -> DbResult<Rc<Vec<Skin>>> {
+        if self.catalog.is_none() {
+            self.catalog = Some(Rc::new(db.list_skins()?));
+        }
+        Ok(self.catalog.clone().unwrap())
+    }
+
+    pub fn cases(&mut self, db: &Database) -> DbResult<Rc<Vec<Case>>> {
+        if self.cases.is_none() {
+            self.cases = Some(Rc::new(db.list_cases()?));
+        }
+        Ok(self.cases.clone().unwrap())
+    }
+
+    /// Catalog skins matching `filter`. Only the latest search is kept, so this
+    /// queries again when the filter changes.
+    pub fn search(&mut self, db: &Database, filter: &SkinFilter) -> DbResult<Rc<Vec<Skin>>> {
+        if let Some((cached, skins)) = &self.search
+            && cached == filter
+        {
+            return Ok(skins.clone());
+        }
+        let skins = Rc::new(db.search_skins(filter)?);
+        self.search = Some((filter.clone(), skins.clone()));
+        Ok(skins)
+    }
+
+    pub fn collections(&mut self, db: &Database) -> DbResult<Rc<Vec<String>>> {
+        if self.collections.is_none() {
+            self.collections = Some(Rc::new(db.list_collections()?));
+        }
+        Ok(self.collections.clone().unwrap())
+    }
+
+    pub fn weapon_types(&mut self, db: &Database) -> DbResult<Rc<Vec<String>>> {
+        if self.weapon_types.is_none() {
+            self.weapon_types = Some(Rc::new(db.list_weapon_types()?));
+        }
+        Ok(self.weapon_types.clone().unwrap())
+    }
+
+    pub fn inventory(&mut self, db: &Database, user_id: i64) -> DbResult<Rc<Vec<OwnedSkin>>> {
+        let user = self.user_state(user_id);
+        if user.inventory.is_none() {
+            user.inventory = Some(Rc::new(db.get_inventory_for_user(user_id)?));
+        }
+        Ok(user.inventory.clone().unwrap())
+    }
+
+    /// Worth of the user's inventory and its breakdowns.
+    pub fn inventory_value(&mut self, db: &Database, user_id: i64) -> DbResult<Rc<InventoryValue>> {
+        let user = self.user_state(user_id);
+        if user.value.is_none() {
+            user.value = Some(Rc::new(db.inventory_value(user_id, TOP_ITEMS)?));
+        }
+        Ok(user.value.clone().unwrap())
+    }
+
+    pub fn balance(&mut self, db: &Database, user_id: i64) -> DbResult<Money> {
+        let user = self.user_state(user_id);
+        if user.balance.is_none() {
+            let found = db.get_user_by_id(user_id)?.ok_or_else(|| "User not found".to_string())?;
+            user.balance = Some(found.balance);
+        }
+        Ok(user.balance.unwrap())
+    }
+
+    /// Forget the user's inventory, its value and balance, e.g. after a buy, sale, case or
+    /// trade-up changed them.
+    pub fn invalidate_user(&mut self) {
+        self.user = None;
+    }
+
+    /// Forget the catalog, searches and cases, e.g. after a skin was added to the catalog.
+    pub fn invalidate_catalog(&mut self) {
+        self.catalog = None;
+        self.cases = None;
+        self.search = None;
+        self.collections = None;
+        self.weapon_types = None;
+    }
+
+    /// Forget everything; the next read of each part goes to the database.
+    pub fn clear(&mut self) {
+        *self = AppState::default();
+    }
+
+    fn user_state(&mut self, user_id: i64) -> &mut UserState {
+        if self.user.as_ref().is_none_or(|u| u.user_id != user_id) {
+            self.user = Some(UserState { user_id, inventory: None, value: None, balance: None });
+        }
+        self.user.as_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{temp_db, user_and_skin};
+    use crate::models::Variant;
+
+    #[test]
+    fn reads_are_cached_until_invalidated() {
+        let db = temp_db("state_cache");
+        let (user_id, skin_id) = user_and_skin(&db);
+        let mut state = AppState::default();
+
+        let catalog = state.catalog(&db).unwrap();
+        assert!(Rc::ptr_eq(&catalog, &state.catalog(&db).unwrap()));
+        assert!(state.inventory(&db, user_id).unwrap().is_empty());
+        assert_eq!(state.balance(&db, user_id).unwrap(), Money::from_cents(10000));
+
+        // Changes made behind the cache's back stay invisible...
+        db.add_inventory_item(user_id, skin_id, 0.2, Variant::Normal).unwrap();
+        assert!(state.inventory(&db, user_id).unwrap().is_empty());
+
+        // ...until it is invalidated
+        state.invalidate_user();
+        assert_eq!(state.inventory(&db, user_id).unwrap().len(), 1);
+
+        // Another user never sees the previous user's cache
+        let other = db.create_user(&Default::default(), "other", "secret12").unwrap();
+        assert!(state.inventory(&db, other.id).unwrap().is_empty());
+    }
+}
@@ -1,5 +1,25 @@
 use eframe::egui;
 use crate::AuthMode;
+use crate::accounts::AuthError;
+
+/// Message shown to the user for an account error.
+pub fn error_message(e: &AuthError) -> String {
+    match e {
+        AuthError::UsernameLength { min, max } => format!("Usernames must be {} to {} characters long.", min, max),
+        AuthError::UsernameChars { symbols } => format!("Usernames may only contain letters, digits and {}", symbols),
+        AuthError::UsernameTaken => String::from("That username is already taken."),
+        AuthError::PasswordTooShort { min } => format!("Passwords must be at least {} characters long.", min),
+        AuthError::PasswordTooWeak => String::from("Passwords need at least one letter and one digit."),
+        AuthError::InvalidCredentials => String::from("Invalid username or password"),
+        AuthError::IncorrectPassword => String::from("That password is not correct."),
+        AuthError::LockedOut { retry_after_secs } => format!(
+            "Too many failed logins. Try again in {} min.",
+            (retry_after_secs + 59) / 60
+        ),
+        AuthError::UserNotFound => String::from("This account no longer exists."),
+        AuthError::Storage(e) => format!("DB error: {}", e),
+    }
+}
 
 pub fn show_auth(app: &mut crate::CsApp, ctx: &egui::Context, mode: AuthMode) {
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -45,40 +65,58 @@ pub fn show_auth(app: &mut crate::CsApp, ctx: &egui::Context, mode: AuthMode) {
             ui.label("Password");
             ui.add_sized([220.0, input_h], egui::TextEdit::singleline(&mut app.password).password(true));
 
+            if let AuthMode::Login = mode {
+                ui.add_space(spacing * 0.5);
+                ui.checkbox(&mut app.remember_me, "Remember me");
+            }
+
+            if let AuthMode::Register = mode {
+                let rules = &app.account_rules;
+                ui.small(format!(
+                    "{}-{} characters (letters, digits, {}); password at least {} characters with a letter and a digit",
+                    rules.min_username_len, rules.max_username_len, rules.username_symbols, rules.min_password_len
+                ));
+            }
+
             ui.add_space(spacing * 1.5);
 
             // --- Buttons ---
             ui.spacing_mut().item_spacing = egui::vec2(0.0, spacing);
 
-            if ui.add_sized([220.0, button_h], egui::Button::new("Submit")).clicked() {
+            let filled = !app.username.trim().is_empty() && !app.password.is_empty();
+            if ui.add_enabled(filled, egui::Button::new("Submit").min_size(egui::vec2(220.0, button_h))).clicked() {
                 match mode {
                     AuthMode::Login => {
-                        match crate::db::authenticate(&app.db_path, &app.username, &app.password) {
-                            Ok(Some(user)) => {
+                        match app.db.authenticate(&app.account_rules, &app.username, &app.password) {
+                            Ok(user) => {
                                 // Save logged-in username to the app state so other screens can reference it
                                 app.username = user.username.clone();
                                 app.current_user_id = Some(user.id);
                                 app.screen = crate::Screen::LoggedIn(user.username.clone());
                                 app.message.clear();
                                 app.password.clear();
-                            }
-                            Ok(None) => {
-                                app.message = String::from("Invalid username or password");
+                                if app.remember_me {
+                                    match crate::session::remember(&app.db, user.id) {
+                                        Ok(token) => app.session_token = Some(token),
+                                        Err(e) => app.message = format!("Could not remember login: {}", e),
+                                    }
+                                }
                             }
                             Err(e) => {
-                                app.message = format!("DB error: {}", e);
+                                app.message = error_message(&e);
+                                app.password.clear();
                             }
                         }
                     }
                     AuthMode::Register => {
-                        match crate::db::create_user(&app.db_path, &app.username, &app.password) {
+                        match app.db.create_user(&app.account_rules, app.username.trim(), &app.password) {
                             Ok(_user) => {
                                 app.message = String::from("Registration successful. You can now log in.");
                                 app.screen = crate::Screen::MainMenu;
                                 app.password.clear();
                             }
                             Err(e) => {
-                                app.message = format!("Failed to register: {}", e);
+                                app.message = error_message(&e);
                             }
                         }
                     }
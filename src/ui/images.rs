@@ -0,0 +1,274 @@
+//! Skin and case images as egui textures. Images are decoded on a background
+//! thread and kept in an LRU cache bounded by texture memory; images that fail to
+//! load are remembered so they are not retried every frame.
+
+use eframe::egui;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Texture memory kept before the least recently drawn images are dropped.
+pub const DEFAULT_BUDGET_BYTES: usize = 64 * 1024 * 1024;
+
+/// A loaded texture and the original image size, for aspect-correct drawing.
+#[derive(Clone)]
+pub struct TextureInfo {
+    pub handle: egui::TextureHandle,
+    pub size: [usize; 2],
+}
+
+/// What the cache has for an image right now.
+pub enum ImageState {
+    Ready(TextureInfo),
+    /// Being decoded; draw a placeholder and ask again next frame.
+    Loading,
+    /// No image, or it could not be decoded.
+    Missing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ImageKey {
+    Skin(i64),
+    Case(i64),
+}
+
+impl ImageKey {
+    fn texture_name(self) -> String {
+        match self {
+            ImageKey::Skin(id) => format!("skin-{}", id),
+            ImageKey::Case(id) => format!("case-{}", id),
+        }
+    }
+}
+
+enum Entry {
+    Loading,
+    Missing,
+    Ready { texture: TextureInfo, bytes: usize, last_used: u64 },
+}
+
+/// Encoded image bytes queued for the decoder thread.
+type Job = (ImageKey, Vec<u8>);
+
+/// Result sent back by the decoder thread; `None` when the bytes are not an image.
+type Decoded = (ImageKey, Option<egui::ColorImage>);
+
+/// Texture cache held by `CsApp`. Screens ask for images with `skin_texture` /
+/// `case_texture` every frame and draw whatever state comes back.
+pub struct ImageCache {
+    entries: HashMap<ImageKey, Entry>,
+    budget_bytes: usize,
+    used_bytes: usize,
+    // Bumped on every lookup; an entry's `last_used` orders eviction
+    clock: u64,
+    // Started on first use, since the thread needs the egui context to request repaints
+    decoder: Option<(Sender<Job>, Receiver<Decoded>)>,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        ImageCache::new(DEFAULT_BUDGET_BYTES)
+    }
+}
+
+impl ImageCache {
+    pub fn new(budget_bytes: usize) -> Self {
+        ImageCache { entries: HashMap::new(), budget_bytes, used_bytes: 0, clock: 0, decoder: None }
+    }
+
+    /// The image of catalog skin `skin_id`. The first call reads it from the
+    /// database and queues it for decoding.
+    pub fn skin_texture(&mut self, ctx: &egui::Context, db: &crate::db::Database, skin_id: i64) -> ImageState {
+        let key = ImageKey::Skin(skin_id);
+        if let Some(state) = self.lookup(ctx, key) {
+            return state;
+        }
+        match db.skin_image(skin_id) {
+            Ok(Some(bytes)) => self.decode(ctx, key, bytes),
+            _ => {
+                self.entries.insert(key, Entry::Missing);
+                ImageState::Missing
+            }
+        }
+    }
+
+    /// The image of `case`, stored as base64 text on the case.
+    pub fn case_texture(&mut self, ctx: &egui::Context, case: &crate::models::Case) -> ImageState {
+        let key = ImageKey::Case(case.id);
+        if let Some(state) = self.lookup(ctx, key) {
+            return state;
+        }
+        match case.image_base64.as_deref().and_then(crate::db::decode_image_base64) {
+            Some(bytes) => self.decode(ctx, key, bytes),
+            None => {
+                self.entries.insert(key, Entry::Missing);
+                ImageState::Missing
+            }
+        }
+    }
+
+    /// Collect finished decodes, then report the cached state of `key`, if any.
+    fn lookup(&mut self, ctx: &egui::Context, key: ImageKey) -> Option<ImageState> {
+        self.poll(ctx);
+        self.clock += 1;
+        match self.entries.get_mut(&key)? {
+            Entry::Loading => Some(ImageState::Loading),
+            Entry::Missing => Some(ImageState::Missing),
+            Entry::Ready { texture, last_used, .. } => {
+                *last_used = self.clock;
+                Some(ImageState::Ready(texture.clone()))
+            }
+        }
+    }
+
+    fn decode(&mut self, ctx: &egui::Context, key: ImageKey, bytes: Vec<u8>) -> ImageState {
+        let (jobs, _) = self.decoder.get_or_insert_with(|| start_decoder(ctx.clone()));
+        if jobs.send((key, bytes)).is_err() {
+            self.entries.insert(key, Entry::Missing);
+            return ImageState::Missing;
+        }
+        self.entries.insert(key, Entry::Loading);
+        ImageState::Loading
+    }
+
+    /// Turn decoded images into textures and evict down to the budget.
+    fn poll(&mut self, ctx: &egui::Context) {
+        let Some((_, results)) = &self.decoder else { return };
+        let finished: Vec<Decoded> = results.try_iter().collect();
+        for (key, image) in finished {
+            let Some(image) = image else {
+                self.entries.insert(key, Entry::Missing);
+                continue;
+            };
+            let size = image.size;
+            let bytes = size[0] * size[1] * 4;
+            let handle = ctx.load_texture(key.texture_name(), image, egui::TextureOptions::default());
+            self.clock += 1;
+            let texture = TextureInfo { handle, size };
+            self.entries.insert(key, Entry::Ready { texture, bytes, last_used: self.clock });
+            self.used_bytes += bytes;
+            self.evict(key);
+        }
+    }
+
+    /// Drop least recently used textures until the cache fits its budget, keeping
+    /// `keep` (the image just loaded) even if it alone is over budget.
+    fn evict(&mut self, keep: ImageKey) {
+        while self.used_bytes > self.budget_bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(k, _)| **k != keep)
+                .filter_map(|(k, e)| match e {
+                    Entry::Ready { last_used, .. } => Some((*last_used, *k)),
+                    _ => None,
+                })
+                .min_by_key(|(last_used, _)| *last_used);
+            let Some((_, key)) = oldest else { break };
+            if let Some(Entry::Ready { bytes, .. }) = self.entries.remove(&key) {
+                self.used_bytes -= bytes;
+            }
+        }
+    }
+}
+
+/// Spawn the decoder thread. It exits when the cache (and its sender) is dropped.
+fn start_decoder(ctx: egui::Context) -> (Sender<Job>, Receiver<Decoded>) {
+    let (job_tx, job_rx) = mpsc::channel::<Job>();
+    let (done_tx, done_rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("image-decoder".into())
+        .spawn(move || {
+            for (key, bytes) in job_rx {
+                let image = image::load_from_memory(&bytes).ok().map(|img| {
+                    let img = img.to_rgba8();
+                    let size = [img.width() as usize, img.height() as usize];
+                    egui::ColorImage::from_rgba_unmultiplied(size, &img.into_raw())
+                });
+                if done_tx.send((key, image)).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        })
+        .expect("spawn image decoder thread");
+    (job_tx, done_rx)
+}
+
+/// Size that fits an image of `size` into a `max_dim` square, keeping its aspect ratio.
+pub fn fit(size: [usize; 2], max_dim: f32) -> egui::Vec2 {
+    let (w, h) = (size[0] as f32, size[1] as f32);
+    if w <= 0.0 || h <= 0.0 {
+        return egui::vec2(max_dim, max_dim);
+    }
+    let ratio = w / h;
+    if ratio >= 1.0 { egui::vec2(max_dim, max_dim / ratio) } else { egui::vec2(max_dim * ratio, max_dim) }
+}
+
+/// Draw `state` within a `max_dim` square: the image, a spinner while it loads, or
+/// `alt` (if not empty) when there is no image.
+pub fn show(ui: &mut egui::Ui, state: ImageState, max_dim: f32, alt: &str) {
+    match state {
+        ImageState::Ready(info) => {
+            ui.add(egui::Image::new((info.handle.id(), fit(info.size, max_dim))));
+        }
+        ImageState::Loading => {
+            ui.add_sized([max_dim, max_dim], egui::Spinner::new());
+        }
+        ImageState::Missing => {
+            if !alt.is_empty() {
+                ui.label(alt);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(side: u32) -> Vec<u8> {
+        let mut out = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(side, side).write_to(&mut out, image::ImageOutputFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    /// Ask for `skin_id` until its decode finishes.
+    fn wait(cache: &mut ImageCache, ctx: &egui::Context, db: &crate::db::Database, skin_id: i64) -> ImageState {
+        for _ in 0..500 {
+            match cache.skin_texture(ctx, db, skin_id) {
+                ImageState::Loading => std::thread::sleep(std::time::Duration::from_millis(5)),
+                state => return state,
+            }
+        }
+        panic!("skin {} never finished decoding", skin_id);
+    }
+
+    #[test]
+    fn decodes_off_thread_and_evicts_least_recently_used() {
+        let db = crate::db::test_support::temp_db("images_cache");
+        let ctx = egui::Context::default();
+        let ids: Vec<i64> = ["A", "B", "C"]
+            .iter()
+            .map(|name| db.add_skin(name, None, crate::models::Money::ZERO, None, None, Some(&png(4)), (0.0, 1.0)).unwrap().id)
+            .collect();
+        let broken = db.add_skin("Broken", None, crate::models::Money::ZERO, None, None, Some(b"nope"), (0.0, 1.0)).unwrap().id;
+
+        // Room for two 4x4 RGBA textures
+        let mut cache = ImageCache::new(2 * 4 * 4 * 4);
+        assert!(matches!(cache.skin_texture(&ctx, &db, ids[0]), ImageState::Loading));
+        assert!(matches!(wait(&mut cache, &ctx, &db, ids[0]), ImageState::Ready(info) if info.size == [4, 4]));
+        wait(&mut cache, &ctx, &db, ids[1]);
+        // Touch A so that B is the least recently used when C arrives
+        wait(&mut cache, &ctx, &db, ids[0]);
+        wait(&mut cache, &ctx, &db, ids[2]);
+        assert_eq!(cache.used_bytes, 2 * 4 * 4 * 4);
+        assert!(cache.entries.contains_key(&ImageKey::Skin(ids[0])));
+        assert!(!cache.entries.contains_key(&ImageKey::Skin(ids[1])));
+
+        // Failures and skins without images are remembered, not retried
+        assert!(matches!(wait(&mut cache, &ctx, &db, broken), ImageState::Missing));
+        assert!(matches!(cache.entries.get(&ImageKey::Skin(broken)), Some(Entry::Missing)));
+        assert!(matches!(cache.skin_texture(&ctx, &db, 9999), ImageState::Missing));
+        assert!(matches!(cache.entries.get(&ImageKey::Skin(9999)), Some(Entry::Missing)));
+    }
+}
@@ -53,13 +53,7 @@ pub fn show_main_menu(app: &mut crate::CsApp, ctx: &egui::Context) {
 }
 
 
-pub fn show_splash(_app: &mut crate::CsApp, ctx: &egui::Context) {
-    // Forward to the dedicated splash animation module which keeps the
-    // animation code separated from the main menu.
-    crate::ui::splash::show_splash(_app, ctx);
-}
-
-pub fn show_logged_in(app: &mut crate::CsApp, ctx: &egui::Context, username: String) {
+pub fn show_logged_in(app: &mut crate::CsApp, ctx: &egui::Context, _username: String) {
     // Top-left small user badge
     egui::TopBottomPanel::top("top_bar").show(ctx, |ui| {
         ui.horizontal(|ui| {
@@ -68,10 +62,10 @@ pub fn show_logged_in(app: &mut crate::CsApp, ctx: &egui::Context, username: Str
             ui.add_space(6.0);
             // Show username and balance (if available)
             let mut label = app.username.clone();
-            if let Some(uid) = app.current_user_id {
-                if let Ok(Some(user)) = crate::db::get_user_by_id(&app.db_path, uid) {
-                    label = format!("{} — ${:.2}", user.username, user.balance);
-                }
+            if let Some(uid) = app.current_user_id
+                && let Ok(balance) = app.state.balance(&app.db, uid)
+            {
+                label = format!("{} — {}", app.username, balance);
             }
             ui.label(egui::RichText::new(label).strong());
         });
@@ -91,7 +85,7 @@ pub fn show_logged_in(app: &mut crate::CsApp, ctx: &egui::Context, username: Str
 
                 ui.spacing_mut().item_spacing = egui::vec2(0.0, spacing);
 
-                let labels = ["Buy", "Sell", "Tradeup", "Open Skins", "Inventory"];
+                let labels = ["Buy", "Sell", "Tradeup", "Open Skins", "Inventory", "History", "Profile"];
 
                 // Stack buttons vertically, centered horizontally. Show a
                 // bold header above the buttons. Use consistent spacing but
@@ -114,6 +108,8 @@ pub fn show_logged_in(app: &mut crate::CsApp, ctx: &egui::Context, username: Str
                                 "Tradeup" => { app.screen = Screen::Tradeup; app.message.clear(); }
                                 "Open Skins" => { app.screen = Screen::OpenSkins; app.message.clear(); }
                                 "Inventory" => { app.screen = Screen::Inventory; app.message.clear(); }
+                                "History" => { app.screen = Screen::History; app.message.clear(); }
+                                "Profile" => { app.screen = Screen::Profile; app.message.clear(); }
                                 _ => {}
                             }
                         }
@@ -128,9 +124,10 @@ pub fn show_logged_in(app: &mut crate::CsApp, ctx: &egui::Context, username: Str
                     // Center Logout under the stacked buttons
                     let logout_w = 140.0;
                     if ui.add_sized([logout_w, 32.0], egui::Button::new("Logout")).clicked() {
-                        app.screen = Screen::MainMenu;
-                        app.username.clear();
-                        app.password.clear();
+                        if let Some(token) = app.session_token.take() {
+                            crate::session::forget(&app.db, &token);
+                        }
+                        app.sign_out();
                         app.message.clear();
                     }
                 });
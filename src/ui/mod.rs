@@ -3,6 +3,7 @@ pub mod auth;
 pub mod splash;
 pub mod screens;
 pub mod rarity;
+pub mod images;
 
 use eframe::egui;
 
@@ -1,27 +1,131 @@
 use eframe::egui;
-use base64::Engine as _;
+
+/// Search box, filter menus and sort controls above the catalog. Edits
+/// `app.buy_filter`; the search itself runs in `db::search_skins`.
+fn show_filter_bar(app: &mut crate::CsApp, ui: &mut egui::Ui) {
+    let collections = app.state.collections(&app.db).unwrap_or_default();
+    let weapon_types = app.state.weapon_types(&app.db).unwrap_or_default();
+    let multiplier = app.buy_variant.price_multiplier() * app.market.ask_multiplier();
+    let form = &mut app.buy_filter;
+
+    ui.horizontal_wrapped(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut form.filter.name).hint_text("🔍 Search skins").desired_width(160.0));
+
+        egui::ComboBox::from_id_source("buy_rarity")
+            .selected_text(form.filter.rarity.map_or("Any rarity", |r| r.label()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut form.filter.rarity, None, "Any rarity");
+                for r in crate::models::Rarity::ALL {
+                    ui.selectable_value(&mut form.filter.rarity, Some(r), crate::ui::rarity::rarity_richtext(r));
+                }
+            });
+
+        egui::ComboBox::from_id_source("buy_collection")
+            .selected_text(form.filter.collection.as_deref().unwrap_or("Any collection"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut form.filter.collection, None, "Any collection");
+                for c in collections.iter() {
+                    ui.selectable_value(&mut form.filter.collection, Some(c.clone()), c);
+                }
+            });
+
+        egui::ComboBox::from_id_source("buy_weapon_type")
+            .selected_text(form.filter.weapon_type.as_deref().unwrap_or("Any weapon"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut form.filter.weapon_type, None, "Any weapon");
+                for w in weapon_types.iter() {
+                    ui.selectable_value(&mut form.filter.weapon_type, Some(w.clone()), w);
+                }
+            });
+
+        ui.label("Price:");
+        ui.add(egui::TextEdit::singleline(&mut form.min_price).hint_text("min $").desired_width(56.0));
+        ui.label("–");
+        ui.add(egui::TextEdit::singleline(&mut form.max_price).hint_text("max $").desired_width(56.0));
+
+        ui.label("Sort:");
+        egui::ComboBox::from_id_source("buy_sort")
+            .selected_text(form.filter.sort.label())
+            .show_ui(ui, |ui| {
+                for s in crate::models::SkinSort::ALL {
+                    ui.selectable_value(&mut form.filter.sort, s, s.label());
+                }
+            });
+        let arrow = if form.filter.descending { "⬇" } else { "⬆" };
+        if ui.button(arrow).on_hover_text("Reverse order").clicked() {
+            form.filter.descending = !form.filter.descending;
+        }
+
+        if ui.button("Clear").clicked() {
+            *form = crate::BuyFilterForm::default();
+        }
+    });
+
+    // Bounds are typed as buy prices of the selected variant; the catalog stores the
+    // Normal price before the spread. Unparsable text is ignored rather than matching nothing.
+    let parse = |text: &str| {
+        let dollars = text.trim().trim_start_matches('$').parse::<f64>().ok()?;
+        Some(crate::models::Money::from_dollars(dollars / multiplier))
+    };
+    form.filter.min_price = parse(&form.min_price);
+    form.filter.max_price = parse(&form.max_price);
+}
+
+/// Live price change against the base price, with the latest moves on hover.
+fn price_trend(app: &crate::CsApp, ui: &mut egui::Ui, skin: &crate::models::Skin) {
+    if skin.base_price.cents() <= 0 || skin.price == skin.base_price {
+        return;
+    }
+    let change = (skin.price.as_dollars() / skin.base_price.as_dollars() - 1.0) * 100.0;
+    let (arrow, color) = if change > 0.0 {
+        ("▲", egui::Color32::from_rgb(90, 190, 90))
+    } else {
+        ("▼", egui::Color32::from_rgb(230, 90, 90))
+    };
+    ui.small(egui::RichText::new(format!("{} {:+.1}%", arrow, change)).color(color)).on_hover_ui(|ui| {
+        ui.label(format!("Base price {} · now {}", skin.base_price, skin.price));
+        for point in app.db.price_history(skin.id, 5).unwrap_or_default() {
+            ui.small(format!("{}  {}  after a {}", point.created_at, point.price, point.kind.as_str()));
+        }
+    });
+}
 
 pub fn show_buy(app: &mut crate::CsApp, ctx: &egui::Context) {
     egui::CentralPanel::default().show(ctx, |ui| {
         ui.heading("Buy Skins");
         ui.add_space(6.0);
 
-        if let Some(user_id) = app.current_user_id {
-            if let Ok(Some(user)) = crate::db::get_user_by_id(&app.db_path, user_id) {
-                ui.label(format!("Balance: ${:.2}", user.balance));
-            }
+        if let Some(user_id) = app.current_user_id
+            && let Ok(balance) = app.state.balance(&app.db, user_id)
+        {
+            ui.label(format!("Balance: {}", balance));
         }
 
+        ui.horizontal(|ui| {
+            ui.label("Variant:");
+            for v in crate::models::Variant::ALL {
+                ui.selectable_value(&mut app.buy_variant, v, v.as_str());
+            }
+        });
+        let variant = app.buy_variant;
+
+        show_filter_bar(app, ui);
+
         ui.separator();
         ui.add_space(6.0);
 
-        let skins = match crate::db::list_skins(&app.db_path) {
+        let skins = match app.state.search(&app.db, &app.buy_filter.filter) {
             Ok(s) => s,
             Err(e) => {
                 ui.label(format!("Failed to load skins: {}", e));
                 return;
             }
         };
+        if skins.is_empty() {
+            ui.label("No skins match the filters.");
+        } else {
+            ui.small(format!("{} skins", skins.len()));
+        }
 
         egui::ScrollArea::vertical()
             .auto_shrink([false; 2])
@@ -58,99 +162,44 @@ pub fn show_buy(app: &mut crate::CsApp, ctx: &egui::Context) {
                                         ui.add_space(4.0);
 
                                         // IMAGE
-                                        if let Some(b64) = &skin.image_base64 {
-                                            let key = format!("skin-{}", skin.id);
-
-                                            if let Some(info) = app.textures.get(&key) {
-                                                let (w, h) = (info.size[0] as f32, info.size[1] as f32);
-                                                let max = 95.0;
-                                                let ratio = w / h;
-                                                let (dw, dh) = if ratio >= 1.0 {
-                                                    (max, max / ratio)
-                                                } else {
-                                                    (max * ratio, max)
-                                                };
-
-                                                ui.add(egui::Image::new(
-                                                    (info.handle.id(), egui::vec2(dw, dh))
-                                                ));
-                                            } else {
-                                                let pure = if b64.starts_with("data:") {
-                                                    b64.split(',').nth(1).unwrap_or("")
-                                                } else {
-                                                    b64
-                                                };
-
-                                                if let Ok(raw) =
-                                                    base64::engine::general_purpose::STANDARD
-                                                        .decode(pure)
-                                                {
-                                                    if let Ok(img) = image::load_from_memory(&raw) {
-                                                        let img = img.to_rgba8();
-                                                        let (w, h) = img.dimensions();
-                                                        let pixels = img.into_raw();
-
-                                                        let color_image = egui::ColorImage::from_rgba_unmultiplied(
-                                                            [w as usize, h as usize],
-                                                            &pixels,
-                                                        );
-
-                                                        let tex = ctx.load_texture(
-                                                            key.clone(),
-                                                            color_image,
-                                                            egui::TextureOptions::default(),
-                                                        );
-
-                                                        app.textures.insert(
-                                                            key.clone(),
-                                                            crate::TextureInfo {
-                                                                handle: tex.clone(),
-                                                                size: [w as usize, h as usize],
-                                                            },
-                                                        );
-
-                                                        let max = 95.0;
-                                                        let ratio = w as f32 / h as f32;
-                                                        let (dw, dh) = if ratio >= 1.0 {
-                                                            (max, max / ratio)
-                                                        } else {
-                                                            (max * ratio, max)
-                                                        };
-
-                                                        ui.add(egui::Image::new(
-                                                            (tex.id(), egui::vec2(dw, dh)),
-                                                        ));
-                                                    }
-                                                }
-                                            }
-                                        }
+                                        let image = app.images.skin_texture(ctx, &app.db, skin.id);
+                                        crate::ui::images::show(ui, image, 95.0, "");
 
                                         ui.add_space(4.0);
                                         ui.label(&skin.name);
 
-                                        if let Some(rarity) = skin.rarity.clone() {
-                                            ui.small(crate::ui::rarity::rarity_richtext(&rarity));
-                                        }
+                                        ui.horizontal_wrapped(|ui| {
+                                            if let Some(rarity) = skin.rarity {
+                                                ui.small(crate::ui::rarity::rarity_richtext(rarity));
+                                            }
+                                            if let Some(badge) = crate::ui::rarity::variant_richtext(variant) {
+                                                ui.small(badge);
+                                            }
+                                        });
+                                        let price = crate::scripts::buy::buy_price(skin, variant, &app.market);
+                                        price_trend(app, ui, skin);
 
                                         ui.with_layout(
                                             egui::Layout::bottom_up(egui::Align::Center),
                                             |ui| {
-                                                if ui.button(format!("Buy  ${:.2}", skin.price)).clicked() {
-                                                    if let Some(uid) = app.current_user_id {
-                                                        match crate::scripts::buy::attempt_buy(
-                                                            &app.db_path,
-                                                            uid,
-                                                            skin.id,
-                                                            skin.price,
-                                                        ) {
-                                                            Ok(_) => {
-                                                                app.message = format!(
-                                                                    "Purchased {} for ${:.2}",
-                                                                    skin.name, skin.price
-                                                                )
-                                                            }
-                                                            Err(e) => app.message = e,
+                                                if ui.button(format!("Buy  {}", price)).clicked()
+                                                    && let Some(uid) = app.current_user_id
+                                                {
+                                                    match crate::scripts::buy::attempt_buy(
+                                                        &app.db,
+                                                        &mut app.state,
+                                                        &app.market,
+                                                        uid,
+                                                        skin.id,
+                                                        variant,
+                                                    ) {
+                                                        Ok(_) => {
+                                                            app.message = format!(
+                                                                "Purchased {} for {}",
+                                                                variant.display_name(&skin.name), price
+                                                            )
                                                         }
+                                                        Err(e) => app.message = e,
                                                     }
                                                 }
                                             },
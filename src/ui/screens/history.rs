@@ -0,0 +1,88 @@
+use eframe::egui;
+
+/// Ledger of the logged-in user: every balance change with its reason, newest
+/// first, filterable by kind, with per-kind totals.
+pub fn show_history(app: &mut crate::CsApp, ctx: &egui::Context) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading("History");
+        ui.add_space(8.0);
+
+        if app.current_user_id.is_none() {
+            ui.label("You must be logged in to view your history.");
+            if ui.button("⬅ Back").clicked() {
+                app.screen = crate::Screen::MainMenu;
+            }
+            return;
+        }
+
+        let user_id = app.current_user_id.unwrap();
+
+        // Kind filter
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Show:");
+            ui.selectable_value(&mut app.history_filter, None, "All");
+            for kind in crate::models::LedgerKind::ALL {
+                ui.selectable_value(&mut app.history_filter, Some(kind), kind.label());
+            }
+        });
+        ui.add_space(6.0);
+
+        // Net totals per kind
+        if let Ok(totals) = app.db.ledger_totals(user_id) {
+            ui.horizontal_wrapped(|ui| {
+                for (kind, total) in totals {
+                    ui.label(format!("{}: {}", kind.label(), total));
+                    ui.add_space(12.0);
+                }
+            });
+            ui.add_space(6.0);
+        }
+
+        let entries = match app.db.list_ledger(user_id, app.history_filter) {
+            Ok(e) => e,
+            Err(e) => { ui.label(format!("Failed to load history: {}", e)); return; }
+        };
+
+        if entries.is_empty() {
+            ui.label("No entries.");
+            return;
+        }
+
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            egui::Grid::new("history_grid").striped(true).num_columns(6).show(ui, |ui| {
+                ui.strong("#");
+                ui.strong("Time (UTC)");
+                ui.strong("Kind");
+                ui.strong("Amount");
+                ui.strong("Skin");
+                ui.strong("Item");
+                ui.end_row();
+
+                for entry in &entries {
+                    ui.label(entry.id.to_string());
+                    ui.label(&entry.created_at);
+                    ui.label(entry.kind.label());
+                    let color = if entry.amount < crate::models::Money::ZERO {
+                        egui::Color32::from_rgb(230, 90, 90)
+                    } else {
+                        egui::Color32::from_rgb(80, 200, 120)
+                    };
+                    let sign = if entry.amount > crate::models::Money::ZERO { "+" } else { "" };
+                    ui.colored_label(color, format!("{}{}", sign, entry.amount));
+                    ui.horizontal(|ui| {
+                        if let Some(skin_id) = entry.skin_id {
+                            let image = app.images.skin_texture(ctx, &app.db, skin_id);
+                            crate::ui::images::show(ui, image, 24.0, "");
+                        }
+                        ui.label(entry.skin_name.as_deref().unwrap_or("—"));
+                    });
+                    ui.label(entry.inventory_id.map(|id| format!("#{}", id)).unwrap_or_else(|| "—".into()));
+                    ui.end_row();
+                }
+            });
+        });
+    });
+
+    // Standard bottom-left back button
+    crate::ui::bottom_left_back(ctx, app, crate::Screen::LoggedIn(app.username.clone()));
+}
@@ -1,5 +1,7 @@
+use crate::models::OwnedSkin;
+use crate::scripts::inventory::Stack;
 use eframe::egui;
-use base64::Engine as _;
+use std::collections::HashSet;
 
 // Render fixed-size placeholder grid (scrollable container will host this)
 fn render_placeholder_grid(ui: &mut egui::Ui, cols: usize, rows: usize) {
@@ -30,6 +32,234 @@ fn render_placeholder_grid(ui: &mut egui::Ui, cols: usize, rows: usize) {
     }
 }
 
+/// Sell one copy and report the outcome in the status line.
+fn sell_one(app: &mut crate::CsApp, user_id: i64, it: &OwnedSkin) {
+    let skin_name = it.skin.as_ref().map(|s| s.name.as_str()).unwrap_or("Unknown");
+    let price = crate::scripts::sell::sale_price(it, &app.market);
+    match crate::scripts::sell::sell_item(&app.db, &mut app.state, &app.market, user_id, it.inventory.id) {
+        Ok(new_bal) => {
+            app.message = format!("Sold {} for {} — balance: {}", it.inventory.variant.display_name(skin_name), price, new_bal);
+        }
+        Err(e) => app.message = format!("Failed to sell item: {}", e),
+    }
+}
+
+/// Sell `ids` in one transaction and report the outcome in the status line.
+fn sell_batch(app: &mut crate::CsApp, user_id: i64, ids: &[i64]) {
+    match crate::scripts::sell::sell_items(&app.db, &mut app.state, &app.market, user_id, ids) {
+        Ok(sale) => {
+            app.message = format!("Sold {} items for {} — balance: {}", sale.sold, sale.total, sale.balance);
+        }
+        Err(e) => app.message = format!("Failed to sell items: {}", e),
+    }
+}
+
+// One tile per stack: image, wear / variant, a ×N badge for duplicates, and a bottom
+// bar to select the whole stack, sell one copy or list the copies
+fn stack_tile(app: &mut crate::CsApp, ui: &mut egui::Ui, ctx: &egui::Context, user_id: i64, stack: &Stack, slot_size: f32) {
+    let (rect, _response) = ui.allocate_exact_size(egui::vec2(slot_size, slot_size), egui::Sense::click());
+    let ids: Vec<i64> = stack.items.iter().map(|it| it.inventory.id).collect();
+    let selected = ids.iter().filter(|id| app.inventory_selection.contains(id)).count();
+
+    // Background tile, outlined while any copy is selected
+    let rounding = egui::Rounding::same(8.0);
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, rounding, ui.visuals().widgets.inactive.bg_fill);
+    if selected > 0 {
+        painter.rect_stroke(rect.shrink(1.0), rounding, ui.visuals().selection.stroke);
+    }
+
+    let first = &stack.items[0];
+    let last = &stack.items[stack.count() - 1];
+    let skin_name = stack.skin().map(|s| s.name.as_str()).unwrap_or("Unknown");
+    let price = crate::scripts::sell::sale_price(first, &app.market);
+
+    // Tile contents
+    ui.allocate_ui_at_rect(rect, |ui| {
+        ui.vertical_centered(|ui| {
+            ui.add_space(slot_size * 0.06);
+
+            // Image (decoded off-thread + cached)
+            match stack.skin() {
+                Some(skin) => {
+                    let image = app.images.skin_texture(ctx, &app.db, skin.id);
+                    if let crate::ui::images::ImageState::Missing = image {
+                        ui.label(egui::RichText::new(skin_name).strong());
+                    } else {
+                        crate::ui::images::show(ui, image, slot_size * 0.6, "");
+                    }
+                }
+                None => {
+                    ui.label(egui::RichText::new(skin_name).strong());
+                }
+            }
+
+            ui.add_space(slot_size * 0.04);
+            ui.horizontal(|ui| {
+                if let Some(badge) = crate::ui::rarity::variant_richtext(first.inventory.variant) {
+                    ui.small(badge);
+                }
+                let floats = if stack.count() == 1 {
+                    format!("{:.4}", first.inventory.float_value)
+                } else {
+                    format!("{:.3}–{:.3}", first.inventory.float_value, last.inventory.float_value)
+                };
+                ui.small(format!("{} · {}", first.inventory.wear.short(), floats))
+                    .on_hover_text(first.inventory.wear.as_str());
+            });
+
+            // Select + price + Sell bottom bar
+            ui.horizontal(|ui| {
+                let mut all = selected == ids.len();
+                if ui.checkbox(&mut all, "").on_hover_text("Select every copy").changed() {
+                    if all {
+                        app.inventory_selection.extend(ids.iter().copied());
+                    } else {
+                        for id in &ids {
+                            app.inventory_selection.remove(id);
+                        }
+                    }
+                }
+                ui.label(price.to_string());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if stack.count() > 1 {
+                        let open = app.inventory_expanded.contains(&stack.key);
+                        if ui.small_button(if open { "▴" } else { "▾" }).on_hover_text("Show the copies").clicked() {
+                            if open {
+                                app.inventory_expanded.remove(&stack.key);
+                            } else {
+                                app.inventory_expanded.insert(stack.key);
+                            }
+                        }
+                    }
+                    let sell = ui.add_sized([48.0, 24.0], egui::Button::new("Sell"));
+                    let sell = if stack.count() > 1 { sell.on_hover_text("Sell the highest-float copy") } else { sell };
+                    if sell.clicked() {
+                        sell_one(app, user_id, last);
+                    }
+                });
+            });
+        });
+    });
+
+    // Count badge, painted last so it sits on top of the image
+    if stack.count() > 1 {
+        ui.painter_at(rect).text(
+            rect.right_top() + egui::vec2(-8.0, 6.0),
+            egui::Align2::RIGHT_TOP,
+            format!("×{}", stack.count()),
+            egui::FontId::proportional(15.0),
+            ui.visuals().strong_text_color(),
+        );
+    }
+}
+
+// The copies of an expanded stack, one row each
+fn stack_copies(app: &mut crate::CsApp, ui: &mut egui::Ui, user_id: i64, stack: &Stack) {
+    let first = &stack.items[0];
+    let skin_name = stack.skin().map(|s| s.name.as_str()).unwrap_or("Unknown");
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width());
+        ui.label(
+            egui::RichText::new(format!(
+                "{} ({}) — {} copies",
+                first.inventory.variant.display_name(skin_name),
+                first.inventory.wear.as_str(),
+                stack.count()
+            ))
+            .strong(),
+        );
+        for it in &stack.items {
+            let id = it.inventory.id;
+            ui.horizontal(|ui| {
+                let mut selected = app.inventory_selection.contains(&id);
+                if ui.checkbox(&mut selected, format!("#{}", id)).changed() {
+                    if selected {
+                        app.inventory_selection.insert(id);
+                    } else {
+                        app.inventory_selection.remove(&id);
+                    }
+                }
+                ui.monospace(format!("{:.6}", it.inventory.float_value));
+                ui.label(crate::scripts::sell::sale_price(it, &app.market).to_string());
+                if ui.small_button("Sell").clicked() {
+                    sell_one(app, user_id, it);
+                }
+            });
+        }
+    });
+}
+
+// Worth of the whole inventory, with the breakdowns folded away under the totals
+fn value_summary(app: &mut crate::CsApp, ui: &mut egui::Ui, user_id: i64) {
+    let value = match app.state.inventory_value(&app.db, user_id) {
+        Ok(value) => value,
+        Err(e) => {
+            ui.label(format!("Could not value inventory: {}", e));
+            return;
+        }
+    };
+    // Share of the total value, for the breakdown columns
+    let share = |v: crate::models::Money| {
+        if value.total.cents() > 0 { format!("{:.1}%", v.as_dollars() / value.total.as_dollars() * 100.0) } else { String::new() }
+    };
+
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width());
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(format!("Worth {}", value.total)).size(18.0).strong());
+            ui.label(format!("· {} items", value.count));
+            if let Some((it, v)) = value.top_items.first() {
+                let name = it.skin.as_ref().map(|s| s.name.as_str()).unwrap_or("Unknown");
+                ui.label(format!("· most valuable: {} ({})", it.inventory.variant.display_name(name), v));
+            }
+        });
+        if value.count == 0 {
+            return;
+        }
+
+        egui::CollapsingHeader::new("Breakdown").id_source("inventory_value_breakdown").show(ui, |ui| {
+            ui.columns(3, |cols| {
+                cols[0].label(egui::RichText::new("By rarity").strong());
+                egui::Grid::new("inventory_value_rarity").striped(true).show(&mut cols[0], |ui| {
+                    for g in &value.by_rarity {
+                        match g.key {
+                            Some(r) => ui.label(crate::ui::rarity::rarity_richtext(r)),
+                            None => ui.label("No rarity"),
+                        };
+                        ui.label(format!("×{}", g.count));
+                        ui.label(g.value.to_string());
+                        ui.small(share(g.value));
+                        ui.end_row();
+                    }
+                });
+
+                cols[1].label(egui::RichText::new("By collection").strong());
+                egui::Grid::new("inventory_value_collection").striped(true).show(&mut cols[1], |ui| {
+                    for g in &value.by_collection {
+                        ui.label(g.key.as_deref().unwrap_or("No collection"));
+                        ui.label(format!("×{}", g.count));
+                        ui.label(g.value.to_string());
+                        ui.small(share(g.value));
+                        ui.end_row();
+                    }
+                });
+
+                cols[2].label(egui::RichText::new("Most valuable").strong());
+                egui::Grid::new("inventory_value_top").striped(true).show(&mut cols[2], |ui| {
+                    for (it, v) in &value.top_items {
+                        let name = it.skin.as_ref().map(|s| s.name.as_str()).unwrap_or("Unknown");
+                        ui.label(it.inventory.variant.display_name(name));
+                        ui.small(it.inventory.wear.short()).on_hover_text(it.inventory.wear.as_str());
+                        ui.label(v.to_string());
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    });
+}
+
 pub fn show_inventory(app: &mut crate::CsApp, ctx: &egui::Context) {
     egui::CentralPanel::default()
         .frame(egui::Frame {
@@ -56,12 +286,54 @@ pub fn show_inventory(app: &mut crate::CsApp, ctx: &egui::Context) {
                 }
 
                 let user_id = app.current_user_id.unwrap();
-                match crate::scripts::inventory::list_inventory(&app.db_path, user_id) {
+                match app.state.inventory(&app.db, user_id) {
                     Ok(items) => {
+                        // Copies of the same skin, wear and variant share one tile
+                        let stacks = crate::scripts::inventory::stack_items(&items);
+                        let duplicates = crate::scripts::inventory::duplicate_ids(&stacks);
+
+                        // Forget selected items that were sold, traded up or refreshed away
+                        let owned: HashSet<i64> = items.iter().map(|it| it.inventory.id).collect();
+                        app.inventory_selection.retain(|id| owned.contains(id));
+                        let selected: Vec<i64> = items
+                            .iter()
+                            .map(|it| it.inventory.id)
+                            .filter(|id| app.inventory_selection.contains(id))
+                            .collect();
+                        // Each sale in the batch lowers the price of the next copy of that skin
+                        let selected_value = crate::scripts::sell::quote_batch(
+                            items.iter().filter(|it| app.inventory_selection.contains(&it.inventory.id)),
+                            &app.market,
+                        );
+
+                        value_summary(app, ui, user_id);
+                        ui.add_space(8.0);
+
                         ui.horizontal(|ui| {
                             if ui.button("🔄 Refresh").clicked() {
+                                // Drop every cached list; they reload from the database next frame
+                                app.state.clear();
                                 app.message = String::from("Refreshed inventory");
                             }
+                            ui.label(format!("{} items in {} stacks", items.len(), stacks.len()));
+                            ui.separator();
+                            if ui.add_enabled(!items.is_empty(), egui::Button::new("Select all")).clicked() {
+                                app.inventory_selection.extend(owned.iter().copied());
+                            }
+                            if ui.add_enabled(!selected.is_empty(), egui::Button::new("Clear selection")).clicked() {
+                                app.inventory_selection.clear();
+                            }
+                            let sell_selected = egui::Button::new(format!("Sell selected ({}) — {}", selected.len(), selected_value));
+                            if ui.add_enabled(!selected.is_empty(), sell_selected).clicked() {
+                                sell_batch(app, user_id, &selected);
+                            }
+                            if ui
+                                .add_enabled(!duplicates.is_empty(), egui::Button::new(format!("Sell all duplicates ({})", duplicates.len())))
+                                .on_hover_text("Keep the lowest-float copy of each stack and sell the rest")
+                                .clicked()
+                            {
+                                sell_batch(app, user_id, &duplicates);
+                            }
                         });
 
                         ui.add_space(8.0);
@@ -71,7 +343,7 @@ pub fn show_inventory(app: &mut crate::CsApp, ctx: &egui::Context) {
                             .auto_shrink([false; 2])
                             .show(ui, |ui| {
                                 ui.vertical(|ui| {
-                                    if items.is_empty() {
+                                    if stacks.is_empty() {
                                         render_placeholder_grid(ui, 4, 3);
                                         return;
                                     }
@@ -79,7 +351,7 @@ pub fn show_inventory(app: &mut crate::CsApp, ctx: &egui::Context) {
                                     let cols = 4;
                                     let spacing = 8.0;
 
-                                    for chunk in items.chunks(cols) {
+                                    for chunk in stacks.chunks(cols) {
                                         // A full row
                                         ui.horizontal(|ui| {
                                             ui.with_layout(
@@ -91,149 +363,21 @@ pub fn show_inventory(app: &mut crate::CsApp, ctx: &egui::Context) {
                                                         / chunk.len() as f32)
                                                         .clamp(48.0, 160.0);
 
-                                                    for it in chunk {
-                                                        let (rect, response) = ui
-                                                            .allocate_exact_size(
-                                                                egui::vec2(slot_size, slot_size),
-                                                                egui::Sense::click(),
-                                                            );
-                                                        let painter = ui.painter_at(rect);
-
-                                                        // Background tile
-                                                        let rounding = egui::Rounding::same(8.0);
-                                                        let bg = ui.visuals().widgets.inactive.bg_fill;
-                                                        painter.rect_filled(rect, rounding, bg);
-
-                                                        // Tile contents
-                                                        ui.allocate_ui_at_rect(rect, |ui| {
-                                                            ui.vertical_centered(|ui| {
-                                                                let skin_name = it.skin.as_ref()
-                                                                    .map(|s| s.name.as_str())
-                                                                    .unwrap_or("Unknown");
-
-                                                                let price = it.skin.as_ref()
-                                                                    .map(|s| s.price)
-                                                                    .unwrap_or(0.0);
-
-                                                                ui.add_space(slot_size * 0.06);
-
-                                                                // Image (decoded + cached)
-                                                                if let Some(skin) = &it.skin {
-                                                                    if let Some(b64) = &skin.image_base64 {
-                                                                        let key = format!("skin-{}", skin.id);
-
-                                                                        if let Some(info) =
-                                                                            app.textures.get(&key)
-                                                                        {
-                                                                            let img_dim =
-                                                                                slot_size * 0.6;
-                                                                            let (w, h) = (
-                                                                                info.size[0] as f32,
-                                                                                info.size[1] as f32,
-                                                                            );
-                                                                            let (dw, dh) =
-                                                                                if w > 0.0 && h > 0.0 {
-                                                                                    let r = w / h;
-                                                                                    if r >= 1.0 {
-                                                                                        (img_dim, img_dim / r)
-                                                                                    } else {
-                                                                                        (img_dim * r, img_dim)
-                                                                                    }
-                                                                                } else {
-                                                                                    (img_dim, img_dim)
-                                                                                };
-                                                                            ui.add(
-                                                                                egui::Image::new((
-                                                                                    info.handle.id(),
-                                                                                    egui::vec2(dw, dh),
-                                                                                )),
-                                                                            );
-                                                                        } else {
-                                                                            // Decode on-demand
-                                                                            let data = if b64.starts_with("data:") {
-                                                                                match b64.find(',') {
-                                                                                    Some(idx) => &b64[idx + 1..],
-                                                                                    None => &b64,
-                                                                                }
-                                                                            } else {
-                                                                                &b64
-                                                                            };
-
-                                                                            if let Ok(raw) = base64::engine::general_purpose::STANDARD.decode(data) {
-                                                                                if let Ok(img) = image::load_from_memory(&raw) {
-                                                                                    let img = img.to_rgba8();
-                                                                                    let (w, h) = img.dimensions();
-                                                                                    let size = [w as usize, h as usize];
-                                                                                    let pixels = img.into_raw();
-                                                                                    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-                                                                                    let handle = ctx.load_texture(
-                                                                                        key.clone(),
-                                                                                        color_image,
-                                                                                        egui::TextureOptions::default(),
-                                                                                    );
-                                                                                    app.textures.insert(
-                                                                                        key.clone(),
-                                                                                        crate::TextureInfo {
-                                                                                            handle: handle.clone(),
-                                                                                            size,
-                                                                                        },
-                                                                                    );
-
-                                                                                    let img_dim = slot_size * 0.6;
-                                                                                    let (dw, dh) = {
-                                                                                        let wf = size[0] as f32;
-                                                                                        let hf = size[1] as f32;
-                                                                                        let r = wf / hf;
-                                                                                        if r >= 1.0 {
-                                                                                            (img_dim, img_dim / r)
-                                                                                        } else {
-                                                                                            (img_dim * r, img_dim)
-                                                                                        }
-                                                                                    };
-                                                                                    ui.add(
-                                                                                        egui::Image::new((handle.id(), egui::vec2(dw, dh))),
-                                                                                    );
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                    } else {
-                                                                        ui.label(egui::RichText::new(skin_name).strong());
-                                                                    }
-                                                                } else {
-                                                                    ui.label(egui::RichText::new(skin_name).strong());
-                                                                }
-
-                                                                ui.add_space(slot_size * 0.04);
-
-                                                                // Price + Sell button bottom bar
-                                                                ui.horizontal(|ui| {
-                                                                    ui.label(format!("${:.2}", price));
-                                                                    ui.with_layout(
-                                                                        egui::Layout::right_to_left(egui::Align::Center),
-                                                                        |ui| {
-                                                                            if ui.add_sized([64.0, 24.0], egui::Button::new("Sell")).clicked() {
-                                                                                if let Some(uid) = app.current_user_id {
-                                                                                    match crate::scripts::sell::sell_item(&app.db_path, uid, it.inventory.id, price) {
-                                                                                        Ok(new_bal) => {
-                                                                                            app.message = format!("Sold {} for ${:.2} — balance: ${:.2}",
-                                                                                                it.skin.as_ref().map(|s| s.name.clone()).unwrap_or("Unknown".into()),
-                                                                                                price, new_bal);
-                                                                                        }
-                                                                                        Err(e) => app.message = format!("Failed to sell item: {}", e),
-                                                                                    }
-                                                                                }
-                                                                            }
-                                                                        },
-                                                                    );
-                                                                });
-                                                            });
-                                                        });
-
+                                                    for stack in chunk {
+                                                        stack_tile(app, ui, ctx, user_id, stack, slot_size);
                                                         ui.add_space(spacing);
                                                     }
                                                 },
                                             );
                                         });
+
+                                        // Expanded stacks list their copies under the row
+                                        for stack in chunk {
+                                            if stack.count() > 1 && app.inventory_expanded.contains(&stack.key) {
+                                                ui.add_space(spacing);
+                                                stack_copies(app, ui, user_id, stack);
+                                            }
+                                        }
                                         ui.add_space(spacing);
                                     }
                                 });
@@ -3,3 +3,5 @@ pub mod sell;
 pub mod tradeup;
 pub mod open_skins;
 pub mod inventory;
+pub mod history;
+pub mod profile;
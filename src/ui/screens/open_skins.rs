@@ -1,8 +1,5 @@
 use eframe::egui;
 use std::time::Instant;
-use base64::Engine as _;
-
-const CASE_COST: f64 = 5.0;
 
 pub fn show_open_skins(app: &mut crate::CsApp, ctx: &egui::Context) {
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -10,11 +7,11 @@ pub fn show_open_skins(app: &mut crate::CsApp, ctx: &egui::Context) {
             ui.heading("Open Case");
             ui.add_space(8.0);
             // Show current balance
-            if let Some(user_id) = app.current_user_id {
-                if let Ok(Some(user)) = crate::db::get_user_by_id(&app.db_path, user_id) {
-                    ui.label(format!("Balance: ${:.2}", user.balance));
-                    ui.add_space(6.0);
-                }
+            if let Some(user_id) = app.current_user_id
+                && let Ok(balance) = app.state.balance(&app.db, user_id)
+            {
+                ui.label(format!("Balance: {}", balance));
+                ui.add_space(6.0);
             }
             if app.current_user_id.is_none() {
                 ui.label("You must be logged in to open cases.");
@@ -55,41 +52,8 @@ pub fn show_open_skins(app: &mut crate::CsApp, ctx: &egui::Context) {
                         let idx = (center + i + state.options.len() - window/2) % state.options.len();
                         let skin = &state.options[idx];
                         // draw thumbnail (best-effort)
-                        let key = format!("skin-{}", skin.id);
-                        if let Some(info) = app.textures.get(&key) {
-                            let (w, h) = (info.size[0] as f32, info.size[1] as f32);
-                            let (dw, dh) = if w > 0.0 && h > 0.0 {
-                                let ratio = w / h;
-                                if ratio >= 1.0 { (max_dim, max_dim / ratio) } else { (max_dim * ratio, max_dim) }
-                            } else { (max_dim, max_dim) };
-                            ui.add(egui::Image::new((info.handle.id(), egui::vec2(dw, dh))));
-                        } else if let Some(b64) = &skin.image_base64 {
-                            let data = if b64.starts_with("data:") {
-                                match b64.find(',') { Some(idx) => &b64[idx + 1..], None => &b64 }
-                            } else { &b64 };
-                            if let Ok(raw) = base64::engine::general_purpose::STANDARD.decode(data) {
-                                if let Ok(img) = image::load_from_memory(&raw) {
-                                    let img = img.to_rgba8();
-                                    let (w, h) = img.dimensions();
-                                    let size = [w as usize, h as usize];
-                                    let pixels = img.into_raw();
-                                    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-                                    let handle = ctx.load_texture(key.clone(), color_image, egui::TextureOptions::default());
-                                    app.textures.insert(key.clone(), crate::TextureInfo { handle: handle.clone(), size });
-                                    let (dw, dh) = if w > 0 && h > 0 {
-                                        let aw = w as f32; let ah = h as f32; let ratio = aw / ah;
-                                        if ratio >= 1.0 { (max_dim, max_dim / ratio) } else { (max_dim * ratio, max_dim) }
-                                    } else { (max_dim, max_dim) };
-                                    ui.add(egui::Image::new((handle.id(), egui::vec2(dw, dh))));
-                                } else {
-                                    ui.label(&skin.name);
-                                }
-                            } else {
-                                ui.label(&skin.name);
-                            }
-                        } else {
-                            ui.label(&skin.name);
-                        }
+                        let image = app.images.skin_texture(ctx, &app.db, skin.id);
+                        crate::ui::images::show(ui, image, max_dim, &skin.name);
                         ui.add_space(6.0);
                     }
                 });
@@ -101,14 +65,25 @@ pub fn show_open_skins(app: &mut crate::CsApp, ctx: &egui::Context) {
                 }
 
                 if state.finished {
-                    ui.label(egui::RichText::new(format!("You won: {}", state.selected.name)).strong());
+                    let won_name = state.selected_item.variant.display_name(&state.selected.name);
+                    ui.label(egui::RichText::new(format!("You won: {}", won_name)).strong());
                     ui.add_space(6.0);
                     // show colored rarity under the name
-                    let r = state.selected.rarity.clone().unwrap_or_default();
-                    ui.label(crate::ui::rarity::rarity_richtext(&r));
+                    if let Some(r) = state.selected.rarity {
+                        ui.label(crate::ui::rarity::rarity_richtext(r));
+                    }
+                    if let Some(badge) = crate::ui::rarity::variant_richtext(state.selected_item.variant) {
+                        ui.label(badge);
+                    }
+                    ui.label(format!("{} ({:.6})", state.selected_item.wear.as_str(), state.selected_item.float_value));
                     ui.add_space(6.0);
                     if ui.button("Collect").clicked() {
-                        app.message = format!("Received {} (inv #{})", state.selected.name, state.selected_inv_id);
+                        app.message = format!(
+                            "Received {} ({}) (inv #{})",
+                            won_name,
+                            state.selected_item.wear.as_str(),
+                            state.selected_item.id
+                        );
                         app.open_case_state = None;
                     }
                 }
@@ -116,15 +91,55 @@ pub fn show_open_skins(app: &mut crate::CsApp, ctx: &egui::Context) {
                 return;
             }
 
-            // No animation running — show open case button
-            ui.label(format!("Open a case for ${:.2}", CASE_COST));
+            // No animation running — let the user pick a case and open it
+            let cases = match app.state.cases(&app.db) {
+                Ok(c) => c,
+                Err(e) => { ui.label(format!("Failed to load cases: {}", e)); return; }
+            };
+            if cases.is_empty() {
+                ui.label("No cases available. Add some to data/cases.json.");
+                return;
+            }
+            if !app.selected_case.is_some_and(|id| cases.iter().any(|c| c.id == id)) {
+                app.selected_case = Some(cases[0].id);
+            }
+
+            ui.horizontal_wrapped(|ui| {
+                for case in cases.iter() {
+                    let label = format!("{}  {}", case.name, case.open_cost());
+                    ui.selectable_value(&mut app.selected_case, Some(case.id), label);
+                }
+            });
+            ui.add_space(6.0);
+
+            let Some(case) = cases.iter().find(|c| Some(c.id) == app.selected_case) else { return };
+            let catalog = app.state.catalog(&app.db).unwrap_or_default();
+            let odds = crate::scripts::open_skins::drop_odds(case, &crate::scripts::open_skins::case_pool(case, &catalog));
+
+            // Case image (decoded + cached like skin thumbnails)
+            let image = app.images.case_texture(ctx, case);
+            crate::ui::images::show(ui, image, 96.0, "");
+
+            ui.label(format!("Case {} + key {}", case.price, case.key_price));
+            ui.add_space(4.0);
+            ui.label(egui::RichText::new("Contains:").strong());
+            for (skin, chance) in &odds {
+                ui.horizontal(|ui| {
+                    ui.label(&skin.name);
+                    if let Some(r) = skin.rarity {
+                        ui.small(crate::ui::rarity::rarity_richtext(r));
+                    }
+                    ui.small(format!("{:.2}%", chance * 100.0));
+                });
+            }
             ui.add_space(6.0);
-            if ui.button("Open Case").clicked() {
+
+            if ui.button(format!("Open {} ({})", case.name, case.open_cost())).clicked() {
                 // perform the case opening logic (select skin and insert inventory)
-                match crate::scripts::open_skins::open_case(&app.db_path, user_id, CASE_COST) {
-                    Ok((inv_id, selected)) => {
-                        // Build an options carousel for animation (sample and include selected)
-                        let mut opts = crate::db::list_skins(&app.db_path).unwrap_or_default();
+                match crate::scripts::open_skins::open_case(&app.db, &mut app.state, &app.case_config, user_id, case.id) {
+                    Ok((item, selected)) => {
+                        // Build an options carousel for animation from the case's drop pool
+                        let mut opts: Vec<crate::models::Skin> = odds.iter().map(|(s, _)| s.clone()).collect();
                         // ensure selected is included
                         if !opts.iter().any(|s| s.id == selected.id) {
                             opts.push(selected.clone());
@@ -136,8 +151,8 @@ pub fn show_open_skins(app: &mut crate::CsApp, ctx: &egui::Context) {
 
                         app.open_case_state = Some(crate::OpenCaseState {
                             options: opts,
-                            selected: selected,
-                            selected_inv_id: inv_id,
+                            selected,
+                            selected_item: item,
                             start: Instant::now(),
                             duration: std::time::Duration::from_secs_f32(2.6),
                             finished: false,
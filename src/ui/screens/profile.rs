@@ -0,0 +1,114 @@
+use eframe::egui;
+
+/// Account management for the logged-in user: rename, change password and
+/// delete the account.
+pub fn show_profile(app: &mut crate::CsApp, ctx: &egui::Context) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.vertical_centered(|ui| {
+            ui.heading("Profile");
+            ui.add_space(8.0);
+
+            if app.current_user_id.is_none() {
+                ui.label("You must be logged in to manage your account.");
+                if ui.button("⬅ Back").clicked() {
+                    app.screen = crate::Screen::MainMenu;
+                }
+                return;
+            }
+
+            let user_id = app.current_user_id.unwrap();
+            let field_w = 220.0;
+
+            ui.label(egui::RichText::new(format!("Logged in as {}", app.username)).strong());
+            ui.add_space(12.0);
+
+            // --- Username ---
+            ui.label(egui::RichText::new("Change username").strong());
+            ui.add_sized([field_w, 24.0], egui::TextEdit::singleline(&mut app.profile.new_username).hint_text("New username"));
+            let can_rename = !app.profile.new_username.trim().is_empty();
+            if ui.add_enabled(can_rename, egui::Button::new("Rename")).clicked() {
+                let new_name = app.profile.new_username.trim().to_string();
+                match app.db.rename_user(&app.account_rules, user_id, &new_name) {
+                    Ok(user) => {
+                        app.message = format!("Username changed to {}", user.username);
+                        app.username = user.username;
+                        app.profile.new_username.clear();
+                    }
+                    Err(e) => app.message = crate::ui::auth::error_message(&e),
+                }
+            }
+            ui.add_space(12.0);
+
+            // --- Password ---
+            ui.label(egui::RichText::new("Change password").strong());
+            ui.add_sized([field_w, 24.0], egui::TextEdit::singleline(&mut app.profile.old_password).password(true).hint_text("Current password"));
+            ui.add_sized([field_w, 24.0], egui::TextEdit::singleline(&mut app.profile.new_password).password(true).hint_text("New password"));
+            ui.add_sized([field_w, 24.0], egui::TextEdit::singleline(&mut app.profile.confirm_password).password(true).hint_text("Repeat new password"));
+            let can_change = !app.profile.old_password.is_empty() && !app.profile.new_password.is_empty();
+            if ui.add_enabled(can_change, egui::Button::new("Change password")).clicked() {
+                if app.profile.new_password != app.profile.confirm_password {
+                    app.message = String::from("New passwords do not match");
+                } else {
+                    match app.db.change_password(
+                        &app.account_rules,
+                        user_id,
+                        &app.profile.old_password,
+                        &app.profile.new_password,
+                    ) {
+                        Ok(()) => app.message = String::from("Password changed"),
+                        Err(e) => app.message = crate::ui::auth::error_message(&e),
+                    }
+                }
+                app.profile.old_password.clear();
+                app.profile.new_password.clear();
+                app.profile.confirm_password.clear();
+            }
+            ui.add_space(12.0);
+
+            // --- Delete account ---
+            ui.label(egui::RichText::new("Delete account").strong());
+            if !app.profile.confirm_delete {
+                if ui.button("Delete account…").clicked() {
+                    app.profile.confirm_delete = true;
+                }
+            } else {
+                ui.colored_label(
+                    egui::Color32::from_rgb(230, 90, 90),
+                    "This removes your account, inventory and history for good.",
+                );
+                ui.add_sized([field_w, 24.0], egui::TextEdit::singleline(&mut app.profile.delete_password).password(true).hint_text("Password"));
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!app.profile.delete_password.is_empty(), egui::Button::new("Delete for good")).clicked() {
+                        match app.db.delete_user(user_id, &app.profile.delete_password) {
+                            Ok(()) => {
+                                // The session rows went with the account; drop the token file too
+                                if app.session_token.take().is_some() {
+                                    crate::session::clear_token(app.db.path());
+                                }
+                                app.sign_out();
+                                app.message = String::from("Account deleted");
+                            }
+                            Err(e) => {
+                                app.profile.delete_password.clear();
+                                app.message = crate::ui::auth::error_message(&e);
+                            }
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        app.profile.confirm_delete = false;
+                        app.profile.delete_password.clear();
+                    }
+                });
+            }
+
+            if !app.message.is_empty() {
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(&app.message);
+            }
+        });
+    });
+
+    // Standard bottom-left back button
+    crate::ui::bottom_left_back(ctx, app, crate::Screen::LoggedIn(app.username.clone()));
+}
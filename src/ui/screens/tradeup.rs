@@ -1,6 +1,84 @@
 use eframe::egui;
 
+/// Right-hand panel previewing the selected contract: every possible output with its
+/// odds and price, plus expected value, profit/loss and chance of profit.
+fn show_odds_panel(app: &mut crate::CsApp, ctx: &egui::Context, items: &[crate::models::OwnedSkin]) {
+    egui::SidePanel::right("tradeup_odds")
+        .resizable(false)
+        .default_width(260.0)
+        .show(ctx, |ui| {
+            ui.heading("Contract odds");
+            ui.add_space(6.0);
+
+            let inputs: Vec<crate::scripts::tradeup::TradeupInput> = items
+                .iter()
+                .filter(|e| app.tradeup_selection.contains(&e.inventory.id))
+                .filter_map(crate::scripts::tradeup::TradeupInput::from_owned)
+                .collect();
+
+            if inputs.is_empty() {
+                ui.label("Select items to preview the possible outcomes.");
+                return;
+            }
+
+            let catalog = match app.state.catalog(&app.db) {
+                Ok(c) => c,
+                Err(e) => { ui.label(format!("Failed to load skins: {}", e)); return; }
+            };
+
+            let cases = app.state.cases(&app.db).unwrap_or_default();
+            let stats = match crate::scripts::tradeup::contract_stats(&inputs, &catalog, &cases, app.tradeup_kind) {
+                Ok(s) => s,
+                Err(e) => { ui.colored_label(egui::Color32::YELLOW, e); return; }
+            };
+
+            egui::ScrollArea::vertical().max_height(ui.available_height() - 110.0).show(ui, |ui| {
+                egui::Grid::new("tradeup_odds_grid").striped(true).show(ui, |ui| {
+                    ui.strong("Skin");
+                    ui.strong("Chance");
+                    ui.strong("Value");
+                    ui.end_row();
+                    for o in &stats.outcomes {
+                        ui.vertical(|ui| {
+                            ui.label(o.variant.display_name(&o.skin.name));
+                            ui.small(format!(
+                                "{} · {:.6}",
+                                crate::models::Wear::from_float(o.float_value).as_str(),
+                                o.float_value
+                            ));
+                        });
+                        ui.label(format!("{:.2}%", o.probability * 100.0));
+                        ui.label(crate::scripts::sell::value_at(&o.skin, o.float_value, o.variant).to_string());
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.separator();
+            ui.label(format!("Input cost: {}", stats.input_cost));
+            ui.label(format!("Expected value: {}", stats.expected_value));
+            let profit_color = if stats.expected_profit >= crate::models::Money::ZERO {
+                egui::Color32::from_rgb(80, 200, 120)
+            } else {
+                egui::Color32::from_rgb(230, 90, 90)
+            };
+            let sign = if stats.expected_profit >= crate::models::Money::ZERO { "+" } else { "" };
+            ui.colored_label(profit_color, format!("Expected profit: {}{}", sign, stats.expected_profit));
+            ui.label(format!("Chance of profit: {:.2}%", stats.profit_chance * 100.0));
+            let needed = app.tradeup_kind.input_count();
+            if inputs.len() != needed {
+                ui.small(format!("Preview based on {} of {} inputs", inputs.len(), needed));
+            }
+        });
+}
+
 pub fn show_tradeup(app: &mut crate::CsApp, ctx: &egui::Context) {
+    if let Some(user_id) = app.current_user_id
+        && let Ok(items) = app.state.inventory(&app.db, user_id)
+    {
+        show_odds_panel(app, ctx, &items);
+    }
+
     egui::CentralPanel::default().show(ctx, |ui| {
         ui.vertical_centered(|ui| {
             ui.heading("Trade Up");
@@ -17,32 +95,44 @@ pub fn show_tradeup(app: &mut crate::CsApp, ctx: &egui::Context) {
             let user_id = app.current_user_id.unwrap();
 
             // Load inventory
-            let items = match crate::db::get_inventory_for_user(&app.db_path, user_id) {
+            let items = match app.state.inventory(&app.db, user_id) {
                 Ok(it) => it,
                 Err(e) => { ui.label(format!("Failed to load inventory: {}", e)); return; }
             };
 
+            // Contract type toggle; switching modes starts a fresh selection
+            ui.horizontal(|ui| {
+                ui.label("Contract:");
+                for kind in crate::scripts::tradeup::ContractKind::ALL {
+                    if ui.selectable_label(app.tradeup_kind == kind, kind.label()).clicked() && app.tradeup_kind != kind {
+                        app.tradeup_kind = kind;
+                        app.tradeup_selection.clear();
+                    }
+                }
+            });
+            ui.add_space(6.0);
+
             // Precompute selection validity so we can keep the Trade Up button visible
+            let kind = app.tradeup_kind;
+            let needed = kind.input_count();
             let selection_count = app.tradeup_selection.len();
-            // Determine if selection is exactly 10 and all same rarity
-            let mut rar: Option<String> = None;
+            // Determine if selection has the right size and all same rarity
+            let mut rar: Option<crate::models::Rarity> = None;
             let mut valid = true;
-            if selection_count != 10 {
+            if selection_count != needed {
                 valid = false;
             } else {
                 for id in &app.tradeup_selection {
-                    if let Some(entry) = items.iter().find(|e| e.inventory.id == *id) {
-                        if let Some(s) = &entry.skin {
-                            if let Some(r0) = &rar {
-                                if r0.to_lowercase() != s.rarity.clone().unwrap_or_default().to_lowercase() {
-                                    valid = false;
-                                    break;
-                                }
-                            } else {
-                                rar = Some(s.rarity.clone().unwrap_or_default());
-                            }
-                        } else { valid = false; break; }
-                    } else { valid = false; break; }
+                    let rarity = items
+                        .iter()
+                        .find(|e| e.inventory.id == *id)
+                        .and_then(|e| e.skin.as_ref())
+                        .and_then(|s| s.rarity);
+                    match (rarity, rar) {
+                        (None, _) => { valid = false; break; }
+                        (Some(r), Some(r0)) if r != r0 => { valid = false; break; }
+                        (Some(r), _) => rar = Some(r),
+                    }
                 }
             }
 
@@ -51,15 +141,22 @@ pub fn show_tradeup(app: &mut crate::CsApp, ctx: &egui::Context) {
                     app.tradeup_selection.clear();
                 }
                 ui.add_space(8.0);
-                ui.label(format!("Selected: {} (need 10)", selection_count));
+                ui.label(format!("Selected: {} (need {})", selection_count, needed));
 
                 ui.add_space(12.0);
                 if ui.add_enabled(valid, egui::Button::new("Trade Up")).clicked() {
                     let ids = app.tradeup_selection.clone();
-                    match crate::scripts::tradeup::compose_tradeup(&app.db_path, user_id, ids) {
-                        Ok(new_id) => {
-                            app.message = format!("Tradeup succeeded: new inventory id {}", new_id);
+                    match crate::scripts::tradeup::compose_tradeup(&app.db, &mut app.state, user_id, ids, kind) {
+                        Ok(result) => {
+                            app.message = format!(
+                                "Tradeup succeeded: {} — {} (float {:.6}), inventory id {}",
+                                result.item.variant.display_name(&result.skin.name),
+                                result.item.wear.as_str(),
+                                result.item.float_value,
+                                result.item.id
+                            );
                             app.tradeup_selection.clear();
+                            app.last_tradeup = Some(result);
                         }
                         Err(e) => app.message = format!("Tradeup failed: {}", e),
                     }
@@ -84,27 +181,24 @@ pub fn show_tradeup(app: &mut crate::CsApp, ctx: &egui::Context) {
                         }
 
                         // thumbnail small
-                            if let Some(skin) = &it.skin {
-                                let key = format!("skin-{}", skin.id);
-                                if let Some(info) = app.textures.get(&key) {
-                                    let max_dim = 40.0_f32;
-                                    let (w, h) = (info.size[0] as f32, info.size[1] as f32);
-                                    let (dw, dh) = if w > 0.0 && h > 0.0 {
-                                        let ratio = w / h;
-                                        if ratio >= 1.0 { (max_dim, max_dim / ratio) } else { (max_dim * ratio, max_dim) }
-                                    } else { (max_dim, max_dim) };
-                                    ui.add(egui::Image::new((info.handle.id(), egui::vec2(dw, dh))));
-                                } else {
-                                    ui.label(&skin.name);
-                                }
-                                ui.vertical(|ui| {
-                                    ui.label(&skin.name);
-                                    let r = skin.rarity.clone().unwrap_or_default();
-                                    ui.small(crate::ui::rarity::rarity_richtext(&r));
+                        if let Some(skin) = &it.skin {
+                            let image = app.images.skin_texture(ctx, &app.db, skin.id);
+                            crate::ui::images::show(ui, image, 40.0, &skin.name);
+                            ui.vertical(|ui| {
+                                ui.label(&skin.name);
+                                ui.horizontal(|ui| {
+                                    if let Some(r) = skin.rarity {
+                                        ui.small(crate::ui::rarity::rarity_richtext(r));
+                                    }
+                                    if let Some(badge) = crate::ui::rarity::variant_richtext(it.inventory.variant) {
+                                        ui.small(badge);
+                                    }
+                                    ui.small(format!("{} · {:.6}", it.inventory.wear.as_str(), it.inventory.float_value));
                                 });
-                            } else {
-                                ui.label(format!("inv {} — Unknown", id));
-                            }
+                            });
+                        } else {
+                            ui.label(format!("inv {} — Unknown", id));
+                        }
                     });
                     ui.add_space(6.0);
                 }
@@ -115,8 +209,38 @@ pub fn show_tradeup(app: &mut crate::CsApp, ctx: &egui::Context) {
             // Show message about validity below the list
             if !valid {
                 ui.colored_label(egui::Color32::YELLOW, "Selected items must be valid and of the same rarity to enable Trade Up.");
-            } else if let Some(rstr) = &rar {
-                ui.label(format!("Ready to trade up 10 items of rarity: {}", rstr));
+            } else if let Some(r) = rar {
+                ui.label(format!("Ready to trade up {} items of rarity: {}", needed, r.label()));
+                // Float crafting aid: the output float is this value mapped onto the result's range
+                let inputs: Vec<crate::scripts::tradeup::TradeupInput> = items
+                    .iter()
+                    .filter(|e| app.tradeup_selection.contains(&e.inventory.id))
+                    .filter_map(crate::scripts::tradeup::TradeupInput::from_owned)
+                    .collect();
+                ui.label(format!(
+                    "Average normalized input float: {:.6}",
+                    crate::scripts::tradeup::average_normalized_float(&inputs)
+                ));
+            }
+
+            // Outcome distribution of the last contract, with the received skin highlighted
+            if let Some(result) = &app.last_tradeup {
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(egui::RichText::new("Last contract odds").strong());
+                for outcome in &result.outcomes {
+                    let text = format!(
+                        "{:>6.2}%  {} ({:.6})",
+                        outcome.probability * 100.0,
+                        outcome.variant.display_name(&outcome.skin.name),
+                        outcome.float_value
+                    );
+                    if outcome.skin.id == result.skin.id {
+                        ui.label(egui::RichText::new(format!("▶ {}", text)).strong());
+                    } else {
+                        ui.label(text);
+                    }
+                }
             }
 
             ui.add_space(12.0);
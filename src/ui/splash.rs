@@ -80,12 +80,10 @@ pub fn show_splash(app: &mut crate::CsApp, ctx: &egui::Context) {
             }
 
             // Slight dark vignette and center focus to make text/spinner readable
-            let base = outer_rect.width().min(outer_rect.height());
             // Shaded rectangular area covering the spinner and the loading text
             let spinner_radius = 32.0_f32;
             let pad = 8.0_f32;
             // Text offsets used elsewhere in this file
-            let text_x_offset = -110.0_f32;
             let title_y_offset = 44.0_f32;
             let status_vgap = 26.0_f32; // distance from title to status
             let status_font_h = 14.0_f32;